@@ -1,92 +1,476 @@
-use std::io::{self, stdout};
+use std::{
+    io::{self, stdout},
+    path::PathBuf,
+};
 
+use clap::ValueEnum;
 use ratatui::{
     crossterm::event::KeyCode,
     prelude::{Backend, CrosstermBackend},
     Frame, Terminal,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    game::{GameStats, LiveGame, NextState},
-    input::read_key_block,
-    layout::GameStatsScreen,
-    welcome::{StartScreen, StartScreenAction},
+    cli::Cli,
+    config::Config,
+    daily::{begin_daily, record_daily, LeaderboardAction, LeaderboardScreen},
+    game::{race_text, LiveGame, NextState},
+    ghost::GhostMode,
+    history::{append_result, load_history, GameResult},
+    input::{read_key, read_key_block},
+    keys::add_key_stats,
+    layout::{GameStatsScreen, Theme},
+    playback::{ReplayAction, ReplayScreen},
+    profile::{set_last_profile, Profile},
+    quotes::QuoteLength,
+    race::{LobbyAction, Race},
+    stats::{is_personal_best, StatsAction, StatsScreen},
+    welcome::{NewProfileScreen, ProfileAction, StartScreen, StartScreenAction},
 };
 
-pub fn start_game() -> io::Result<()> {
+pub fn start_game(cli: Cli) -> io::Result<()> {
     use ratatui::crossterm::{
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
         ExecutableCommand,
     };
+    let mut profile = cli.profile()?;
+    let mut warnings = vec![];
+    if let Err(e) = profile.create().and(set_last_profile(&profile)) {
+        warnings.push(format!("Could not open profile {}: {}", profile.name(), e));
+    }
+    let (mut config, mut game_options, config_warnings) = Config::load(&profile);
+    warnings.extend(config_warnings);
+    cli.apply(&mut game_options);
+    if game_options.mode == GameMode::File {
+        let path = game_options.file.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "--mode file needs --file")
+        })?;
+        std::fs::metadata(path)?;
+    }
+
+    let race = match (cli.host, &cli.join) {
+        (Some(port), _) => Some(Race::host(
+            port,
+            &cli.racer_name(),
+            race_text(&game_options, &profile)?,
+        )?),
+        (None, Some(address)) => Some(Race::join(address, &cli.racer_name())?),
+        (None, None) => None,
+    };
+
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    let mut game_options = GameOptions { time: 60 };
-    App::new(&mut game_options).run(&mut terminal)?;
+    let initial_profile = profile.clone();
+    let mut app = App::new(
+        &mut game_options,
+        &mut profile,
+        !cli.has_overrides(),
+        warnings,
+    );
+    app.json_result = cli.json_result;
+    let result = if let Some(race) = race {
+        app.lobby(race).run(&mut terminal)
+    } else if cli.has_overrides() {
+        app.start_game().run(&mut terminal)
+    } else {
+        app.run(&mut terminal)
+    };
 
-    // TODO: save options
+    // After switching profiles the options are that profile's own.
+    let saved = if profile != initial_profile {
+        Config::load(&profile).0.save(&game_options, &profile)
+    } else if cli.has_overrides() {
+        Ok(())
+    } else {
+        config.save(&game_options, &profile)
+    };
 
     disable_raw_mode()?;
     stdout().execute(LeaveAlternateScreen)?;
+    if let Err(e) = saved {
+        eprintln!("Could not save options: {}", e);
+    }
+    if cli.json_result {
+        let result = result?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Interrupted, "no test was finished"))?;
+        println!("{}", serde_json::to_string(&result)?);
+    } else {
+        result?;
+    }
     Ok(())
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    Time,
+    Words,
+    Quote,
+    File,
+    Code,
+    /// Words targeting the keys with the worst accuracy and latency.
+    Practice,
+    /// The same words for everyone on a given day, scored once.
+    Daily,
+}
+
 pub struct GameOptions {
+    pub mode: GameMode,
     pub time: u32,
+    pub words: u32,
+    pub quote_length: QuoteLength,
+    pub language: String,
+    pub file: Option<PathBuf>,
+    pub theme: Theme,
+    pub live_stats: bool,
+    pub allow_backspace: bool,
+    /// Add capitalization and punctuation to generated words.
+    pub punctuation: bool,
+    /// Mix numbers into generated words.
+    pub numbers: bool,
+    /// Snippet collection used in code mode.
+    pub code_language: String,
+    /// Fill in leading indentation in code mode instead of typing it.
+    pub skip_indent: bool,
+    pub ghost: GhostMode,
+    /// Speed of the ghost in pace mode.
+    pub ghost_wpm: u32,
+    /// Generate the same words every time instead of new ones for every test.
+    pub seed: Option<u64>,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            mode: GameMode::Time,
+            time: 60,
+            words: 25,
+            quote_length: QuoteLength::Any,
+            language: "english".to_string(),
+            file: None,
+            theme: Theme::Default,
+            live_stats: true,
+            allow_backspace: true,
+            punctuation: false,
+            numbers: false,
+            code_language: "rust".to_string(),
+            skip_indent: true,
+            ghost: GhostMode::Off,
+            ghost_wpm: 60,
+            seed: None,
+        }
+    }
+}
+
+fn new_game(options: &GameOptions, profile: &Profile) -> AppState {
+    match LiveGame::new(options, profile) {
+        Ok(live_game) => AppState::LiveGame(Box::new(live_game)),
+        Err(e) => AppState::StartScreen(StartScreen::with_warnings(
+            profile,
+            vec![format!("Could not start test: {}", e)],
+        )),
+    }
 }
 
 enum AppState {
     StartScreen(StartScreen),
-    LiveGame(LiveGame),
+    LiveGame(Box<LiveGame>),
     EndGameScreen(GameStatsScreen),
+    StatsScreen(StatsScreen),
+    Replay(Box<ReplayScreen>),
+    Lobby(Box<Race>),
+    Leaderboard(LeaderboardScreen),
+    NewProfile(NewProfileScreen),
+}
+
+/// Waits for a key on the end screen, polling the race while others finish.
+fn end_screen_key(screen: &mut GameStatsScreen) -> io::Result<Option<KeyCode>> {
+    match screen.race_mut() {
+        Some(race) => {
+            race.poll();
+            read_key()
+        }
+        None => read_key_block().map(Some),
+    }
 }
 
 pub struct App<'a> {
     options: &'a mut GameOptions,
+    profile: &'a mut Profile,
+    /// Whether the options are the profile's own and saved when they change
+    /// hands, rather than given on the command line.
+    save_options: bool,
+    /// Exit with the result of the first finished test instead of showing it.
+    json_result: bool,
+    result: Option<Box<GameResult>>,
+    /// Day the daily test under way is scored on, once started, unless the
+    /// day's attempt was already used.
+    daily_attempt: Option<io::Result<Option<u64>>>,
     state: AppState,
 }
 
 impl<'a> App<'a> {
+    fn switch_profile(&mut self, profile: Profile) -> AppState {
+        let mut warnings = vec![];
+        if self.save_options {
+            let (mut config, _, _) = Config::load(self.profile);
+            if let Err(e) = config.save(self.options, self.profile) {
+                warnings.push(format!("Could not save options: {}", e));
+            }
+        }
+        let (_, options, config_warnings) = Config::load(&profile);
+        warnings.extend(config_warnings);
+        if let Err(e) = profile.create().and(set_last_profile(&profile)) {
+            warnings.push(format!("Could not open profile {}: {}", profile.name(), e));
+        }
+        *self.options = options;
+        *self.profile = profile;
+        self.save_options = true;
+        AppState::StartScreen(StartScreen::with_warnings(self.profile, warnings))
+    }
     fn handle_events(mut self) -> io::Result<Option<Self>> {
         let state = match self.state {
             AppState::StartScreen(ref mut start_screen) => match start_screen.handle_events() {
                 StartScreenAction::Continue => self.state,
                 StartScreenAction::Quit => return Ok(None),
-                StartScreenAction::StartGame => {
-                    AppState::LiveGame(LiveGame::new(self.options.time))
+                StartScreenAction::StartGame => new_game(self.options, self.profile),
+                StartScreenAction::ShowStats => {
+                    AppState::StatsScreen(StatsScreen::new(self.profile))
                 }
+                StartScreenAction::SwitchProfile(name) => match Profile::new(&name) {
+                    Ok(profile) => self.switch_profile(profile),
+                    Err(e) => AppState::StartScreen(StartScreen::with_warnings(
+                        self.profile,
+                        vec![e.to_string()],
+                    )),
+                },
+                StartScreenAction::NewProfile => AppState::NewProfile(NewProfileScreen::new()),
                 StartScreenAction::ChangeTime(time) => {
+                    self.options.mode = GameMode::Time;
                     self.options.time = time;
                     self.state
                 }
+                StartScreenAction::ChangeWords(words) => {
+                    self.options.mode = GameMode::Words;
+                    self.options.words = words;
+                    self.state
+                }
+                StartScreenAction::ChangeLanguage(language) => {
+                    self.options.language = language;
+                    self.state
+                }
+                StartScreenAction::ChangeTheme(theme) => {
+                    self.options.theme = theme;
+                    self.state
+                }
+                StartScreenAction::ChangeCode(language) => {
+                    self.options.mode = GameMode::Code;
+                    self.options.code_language = language;
+                    self.state
+                }
+                StartScreenAction::ChangeGhost(ghost) => {
+                    self.options.ghost = ghost;
+                    self.state
+                }
+                StartScreenAction::ChangeGhostPace(wpm) => {
+                    self.options.ghost = GhostMode::Pace;
+                    self.options.ghost_wpm = wpm;
+                    self.state
+                }
+                StartScreenAction::ChangeDaily => {
+                    self.options.mode = GameMode::Daily;
+                    self.state
+                }
+                StartScreenAction::ShowLeaderboard => {
+                    AppState::Leaderboard(LeaderboardScreen::new())
+                }
+                StartScreenAction::ChangeFile => match self.options.file {
+                    Some(_) => {
+                        self.options.mode = GameMode::File;
+                        self.state
+                    }
+                    None => AppState::StartScreen(StartScreen::with_warnings(
+                        self.profile,
+                        vec!["No file to type, give one with --file".to_string()],
+                    )),
+                },
+                StartScreenAction::ChangePractice => {
+                    self.options.mode = GameMode::Practice;
+                    self.state
+                }
+                StartScreenAction::ToggleSkipIndent => {
+                    self.options.skip_indent = !self.options.skip_indent;
+                    self.state
+                }
+                StartScreenAction::TogglePunctuation => {
+                    self.options.punctuation = !self.options.punctuation;
+                    self.state
+                }
+                StartScreenAction::ToggleNumbers => {
+                    self.options.numbers = !self.options.numbers;
+                    self.state
+                }
+                StartScreenAction::ChangeQuote(length) => {
+                    self.options.mode = GameMode::Quote;
+                    self.options.quote_length = length;
+                    self.state
+                }
             },
             AppState::LiveGame(live_game) => match live_game.handle_events()? {
-                NextState::LiveGame(live_game) => AppState::LiveGame(live_game),
-                NextState::Exit => AppState::StartScreen(StartScreen::new()),
-                NextState::GameEnded(GameStats { wpm, acc }) => {
-                    AppState::EndGameScreen(GameStatsScreen::new(wpm, acc))
+                NextState::LiveGame(live_game) => {
+                    let daily = self.options.mode == GameMode::Daily;
+                    if daily && live_game.started() && self.daily_attempt.is_none() {
+                        self.daily_attempt = Some(begin_daily(self.profile.name()));
+                    }
+                    AppState::LiveGame(live_game)
+                }
+                NextState::Exit => {
+                    self.daily_attempt = None;
+                    AppState::StartScreen(StartScreen::new(self.profile))
+                }
+                NextState::GameEnded(game_stats) if self.options.mode == GameMode::Daily => {
+                    let keys_saved = add_key_stats(self.profile, &game_stats.keys);
+                    let attempt = self
+                        .daily_attempt
+                        .take()
+                        .unwrap_or_else(|| begin_daily(self.profile.name()));
+                    let (scored, error) = match attempt {
+                        Ok(Some(day)) => {
+                            match record_daily(&game_stats, self.profile.name(), day) {
+                                Ok(()) => (true, None),
+                                Err(e) => (false, Some(e)),
+                            }
+                        }
+                        Ok(None) => (false, None),
+                        Err(e) => (false, Some(e)),
+                    };
+                    if self.json_result {
+                        self.result = Some(Box::new(GameResult::new(&game_stats, self.options)));
+                    }
+                    AppState::EndGameScreen(
+                        GameStatsScreen::new(*game_stats, false, error)
+                            .with_keys_error(keys_saved.err())
+                            .with_daily(scored),
+                    )
+                }
+                NextState::GameEnded(game_stats) => {
+                    let result = GameResult::new(&game_stats, self.options);
+                    let personal_best = load_history(self.profile)
+                        .map(|history| is_personal_best(&history, &result))
+                        .unwrap_or(false);
+                    let saved = append_result(self.profile, &result);
+                    let keys_saved = add_key_stats(self.profile, &game_stats.keys);
+                    let mut screen = GameStatsScreen::new(*game_stats, personal_best, saved.err())
+                        .with_keys_error(keys_saved.err());
+                    // Keep the replay so later tests can race against it.
+                    if personal_best {
+                        screen.save_replay(self.profile);
+                    }
+                    if self.json_result {
+                        self.result = Some(Box::new(result));
+                    }
+                    AppState::EndGameScreen(screen)
+                }
+                NextState::RaceEnded(game_stats, race) => {
+                    // Race texts don't follow the chosen mode, so only the keys
+                    // are kept.
+                    let keys_saved = add_key_stats(self.profile, &game_stats.keys);
+                    AppState::EndGameScreen(
+                        GameStatsScreen::new(*game_stats, false, None)
+                            .with_keys_error(keys_saved.err())
+                            .with_race(race),
+                    )
+                }
+                NextState::Restart => {
+                    self.daily_attempt = None;
+                    new_game(self.options, self.profile)
                 }
-                NextState::Restart => AppState::LiveGame(LiveGame::new(self.options.time)),
             },
-            AppState::EndGameScreen(_) => loop {
-                let key = read_key_block()?;
-                if key == KeyCode::Tab {
-                    break AppState::LiveGame(LiveGame::new(self.options.time));
+            AppState::Lobby(mut race) => match race.handle_lobby_events()? {
+                LobbyAction::Continue => AppState::Lobby(race),
+                LobbyAction::Start => {
+                    AppState::LiveGame(Box::new(LiveGame::race(race, self.options)))
                 }
-                if key == KeyCode::Esc {
-                    break AppState::StartScreen(StartScreen::new());
+                LobbyAction::Leave => AppState::StartScreen(StartScreen::new(self.profile)),
+            },
+            AppState::EndGameScreen(mut screen) => match end_screen_key(&mut screen)? {
+                None => AppState::EndGameScreen(screen),
+                Some(KeyCode::Tab) => new_game(self.options, self.profile),
+                Some(KeyCode::Esc) => AppState::StartScreen(StartScreen::new(self.profile)),
+                Some(KeyCode::Char('r')) => AppState::Replay(Box::new(ReplayScreen::new(
+                    screen.key_log().clone(),
+                    self.options.theme,
+                    false,
+                ))),
+                Some(KeyCode::Char('s')) => {
+                    screen.save_replay(self.profile);
+                    AppState::EndGameScreen(screen)
                 }
+                _ => AppState::EndGameScreen(screen),
+            },
+            AppState::StatsScreen(mut stats_screen) => match stats_screen.handle_events()? {
+                StatsAction::Continue => AppState::StatsScreen(stats_screen),
+                StatsAction::Back => AppState::StartScreen(StartScreen::new(self.profile)),
+                StatsAction::Replay(log) => {
+                    AppState::Replay(Box::new(ReplayScreen::new(log, self.options.theme, true)))
+                }
+            },
+            AppState::Leaderboard(mut screen) => match screen.handle_events()? {
+                LeaderboardAction::Continue => AppState::Leaderboard(screen),
+                LeaderboardAction::Back => AppState::StartScreen(StartScreen::new(self.profile)),
+            },
+            AppState::NewProfile(ref mut screen) => match screen.handle_events()? {
+                ProfileAction::Continue => self.state,
+                ProfileAction::Cancel => AppState::StartScreen(StartScreen::new(self.profile)),
+                ProfileAction::Create(profile) => self.switch_profile(profile),
+            },
+            AppState::Replay(mut replay) => match replay.handle_events()? {
+                ReplayAction::Continue => AppState::Replay(replay),
+                ReplayAction::Exit if replay.from_history => {
+                    AppState::StatsScreen(StatsScreen::new(self.profile))
+                }
+                ReplayAction::Exit => AppState::StartScreen(StartScreen::new(self.profile)),
             },
         };
         Ok(Some(App { state, ..self }))
     }
-    pub fn new(options: &'a mut GameOptions) -> Self {
+    pub fn new(
+        options: &'a mut GameOptions,
+        profile: &'a mut Profile,
+        save_options: bool,
+        warnings: Vec<String>,
+    ) -> Self {
         App {
+            state: AppState::StartScreen(StartScreen::with_warnings(profile, warnings)),
             options,
-            state: AppState::StartScreen(StartScreen::new()),
+            profile,
+            save_options,
+            json_result: false,
+            result: None,
+            daily_attempt: None,
+        }
+    }
+    pub fn lobby(self, race: Race) -> Self {
+        App {
+            state: AppState::Lobby(Box::new(race)),
+            ..self
+        }
+    }
+    pub fn start_game(self) -> Self {
+        App {
+            state: new_game(self.options, self.profile),
+            ..self
         }
     }
-    pub fn run<B: Backend>(mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
+    /// Runs until the user quits, or until a test ends when asked for its
+    /// result.
+    pub fn run<B: Backend>(
+        mut self,
+        terminal: &mut Terminal<B>,
+    ) -> io::Result<Option<Box<GameResult>>> {
         let mut cursor = None;
         loop {
             let frame = |frame: &mut Frame| {
@@ -97,8 +481,9 @@ impl<'a> App<'a> {
             };
             terminal.draw(frame)?;
             match self.handle_events()? {
+                Some(s) if s.result.is_some() => return Ok(s.result),
                 Some(s) => self = s,
-                None => return Ok(()),
+                None => return Ok(None),
             }
         }
     }
@@ -132,7 +517,12 @@ mod widget {
                     start_screen.render(inner_area, buf, self.options)
                 }
                 AppState::EndGameScreen(game_stats) => game_stats.render(inner_area, buf),
+                AppState::StatsScreen(stats_screen) => stats_screen.render(inner_area, buf),
                 AppState::LiveGame(live_game) => live_game.render(inner_area, buf, state),
+                AppState::Replay(replay) => replay.render(inner_area, buf, state),
+                AppState::Lobby(race) => race.as_ref().render(inner_area, buf),
+                AppState::Leaderboard(screen) => screen.render(inner_area, buf),
+                AppState::NewProfile(screen) => screen.render(inner_area, buf),
             }
         }
     }
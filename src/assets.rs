@@ -0,0 +1,121 @@
+use std::{borrow::Cow, fs, io, path::PathBuf};
+
+pub const LANGUAGES: &str = "languages";
+pub const QUOTES: &str = "quotes";
+pub const CODE: &str = "code";
+
+/// Files shipped with the program, used when no file of the same name is
+/// found on disk.
+const BUNDLED: &[(&str, &str, &str)] = &[
+    (
+        LANGUAGES,
+        "english",
+        include_str!("../languages/english.txt"),
+    ),
+    (
+        LANGUAGES,
+        "english_1k",
+        include_str!("../languages/english_1k.txt"),
+    ),
+    (
+        LANGUAGES,
+        "english_10k",
+        include_str!("../languages/english_10k.txt"),
+    ),
+    (LANGUAGES, "german", include_str!("../languages/german.txt")),
+    (LANGUAGES, "polish", include_str!("../languages/polish.txt")),
+    (
+        LANGUAGES,
+        "russian",
+        include_str!("../languages/russian.txt"),
+    ),
+    (LANGUAGES, "greek", include_str!("../languages/greek.txt")),
+    (
+        LANGUAGES,
+        "japanese",
+        include_str!("../languages/japanese.txt"),
+    ),
+    (
+        LANGUAGES,
+        "chinese",
+        include_str!("../languages/chinese.txt"),
+    ),
+    (QUOTES, "english", include_str!("../quotes/english.txt")),
+    (CODE, "rust", include_str!("../code/rust.txt")),
+    (CODE, "python", include_str!("../code/python.txt")),
+    (CODE, "javascript", include_str!("../code/javascript.txt")),
+];
+
+/// Directories searched for `<kind>/<name>.txt`, in order of priority: the
+/// user data directory, the system share directories and the working directory.
+pub fn search_dirs(kind: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(dir) = dirs::data_dir() {
+        dirs.push(dir.join("speedtype"));
+    }
+    if cfg!(unix) {
+        dirs.push(PathBuf::from("/usr/local/share/speedtype"));
+        dirs.push(PathBuf::from("/usr/share/speedtype"));
+    }
+    dirs.push(PathBuf::new());
+    dirs.into_iter().map(|dir| dir.join(kind)).collect()
+}
+
+pub fn read_asset(kind: &str, name: &str) -> io::Result<Cow<'static, str>> {
+    for dir in search_dirs(kind) {
+        let path = dir.join(format!("{}.txt", name));
+        if path.is_file() {
+            return fs::read_to_string(path).map(Cow::Owned);
+        }
+    }
+    read_bundled(kind, name).map(Cow::Borrowed)
+}
+
+/// The file shipped with the program, ignoring any file on disk.
+pub fn read_bundled(kind: &str, name: &str) -> io::Result<&'static str> {
+    BUNDLED
+        .iter()
+        .find(|(k, n, _)| *k == kind && *n == name)
+        .map(|(_, _, content)| *content)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("`{}` not found", name)))
+}
+
+/// Names of all bundled and on-disk files of the given kind.
+pub fn list_assets(kind: &str) -> Vec<String> {
+    let mut names = search_dirs(kind)
+        .into_iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "txt" {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        })
+        .chain(
+            BUNDLED
+                .iter()
+                .filter(|(k, _, _)| *k == kind)
+                .map(|(_, name, _)| name.to_string()),
+        )
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bundled_files_are_found() {
+        for (kind, name, content) in BUNDLED {
+            assert!(!content.is_empty());
+            assert!(list_assets(kind).iter().any(|n| n == name));
+        }
+        let error = read_asset(LANGUAGES, "missing").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
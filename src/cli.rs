@@ -0,0 +1,203 @@
+use std::{io, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+
+use crate::{
+    app::{GameMode, GameOptions},
+    export::ExportArgs,
+    ghost::GhostMode,
+    profile::{last_profile, Profile},
+    quotes::QuoteLength,
+};
+
+/// Typing speed test in the terminal.
+///
+/// Passing any test option skips the start screen and begins the test right
+/// away. Options given here apply to this run only and are not saved.
+#[derive(Parser, Default)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Kind of test, inferred from the other options if omitted
+    #[arg(long, value_enum)]
+    pub mode: Option<GameMode>,
+    /// Time limit in seconds
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub time: Option<u32>,
+    /// Number of words to type
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: Option<u32>,
+    /// Length of the quote to type
+    #[arg(long, value_enum)]
+    pub quote: Option<QuoteLength>,
+    /// Word list to draw words from
+    #[arg(long)]
+    pub lang: Option<String>,
+    /// Type the contents of a text file, the menu only returns to this one
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+    /// Type code snippets in the given language
+    #[arg(long)]
+    pub code: Option<String>,
+    /// Add capitalization and punctuation to generated words
+    #[arg(long)]
+    pub punctuation: bool,
+    /// Mix numbers into generated words
+    #[arg(long)]
+    pub numbers: bool,
+    /// Race a ghost typing at this speed
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ghost_wpm: Option<u32>,
+    /// Race a replay of the personal best for these options
+    #[arg(long, conflicts_with = "ghost_wpm")]
+    pub ghost_best: bool,
+    /// Generate words from this seed, so the text can be typed again
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Play the daily challenge, the same words for everyone each day
+    #[arg(long)]
+    pub daily: bool,
+    /// Profile whose options and results to use, created if missing
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Print the result as JSON when the test ends and exit, instead of
+    /// showing it
+    #[arg(long, conflicts_with_all = ["host", "join"])]
+    pub json_result: bool,
+    /// Host a race on the local network, listening on PORT
+    #[arg(long, value_name = "PORT", num_args = 0..=1, default_missing_value = "7878")]
+    pub host: Option<u16>,
+    /// Join a race hosted at ADDRESS, such as 192.168.1.20 or localhost:7878
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "host")]
+    pub join: Option<String>,
+    /// Name shown to other racers, the user name by default
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Write the results history as CSV or JSON, then exit
+    Export(ExportArgs),
+}
+
+impl Cli {
+    /// Profile given with `--profile`, or the one used last.
+    pub fn profile(&self) -> io::Result<Profile> {
+        match &self.profile {
+            Some(name) => Profile::new(name),
+            None => Ok(last_profile()),
+        }
+    }
+
+    pub fn has_overrides(&self) -> bool {
+        self.mode.is_some()
+            || self.time.is_some()
+            || self.count.is_some()
+            || self.quote.is_some()
+            || self.lang.is_some()
+            || self.file.is_some()
+            || self.code.is_some()
+            || self.punctuation
+            || self.numbers
+            || self.ghost_wpm.is_some()
+            || self.ghost_best
+            || self.seed.is_some()
+            || self.daily
+    }
+
+    pub fn racer_name(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .unwrap_or_default()
+    }
+
+    pub fn apply(&self, options: &mut GameOptions) {
+        if let Some(time) = self.time {
+            options.mode = GameMode::Time;
+            options.time = time;
+        }
+        if let Some(count) = self.count {
+            options.mode = GameMode::Words;
+            options.words = count;
+        }
+        if let Some(length) = self.quote {
+            options.mode = GameMode::Quote;
+            options.quote_length = length;
+        }
+        if let Some(file) = &self.file {
+            options.mode = GameMode::File;
+            options.file = Some(file.clone());
+        }
+        if let Some(code) = &self.code {
+            options.mode = GameMode::Code;
+            options.code_language = code.clone();
+        }
+        if let Some(mode) = self.mode {
+            options.mode = mode;
+        }
+        if let Some(lang) = &self.lang {
+            options.language = lang.clone();
+        }
+        options.punctuation |= self.punctuation;
+        options.numbers |= self.numbers;
+        if let Some(wpm) = self.ghost_wpm {
+            options.ghost = GhostMode::Pace;
+            options.ghost_wpm = wpm;
+        }
+        if self.ghost_best {
+            options.ghost = GhostMode::Best;
+        }
+        if let Some(seed) = self.seed {
+            options.seed = Some(seed);
+        }
+        if self.daily {
+            options.mode = GameMode::Daily;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cli_overrides_options() {
+        let cli = Cli::parse_from(["speedtype", "--lang", "english_1k", "--count", "50"]);
+        let mut options = GameOptions::default();
+        cli.apply(&mut options);
+        assert!(cli.has_overrides());
+        assert!(options.mode == GameMode::Words);
+        assert_eq!(options.words, 50);
+        assert_eq!(options.language, "english_1k");
+
+        let cli = Cli::parse_from(["speedtype", "--time", "30", "--mode", "words"]);
+        cli.apply(&mut options);
+        assert!(options.mode == GameMode::Words);
+        assert_eq!(options.time, 30);
+
+        let cli = Cli::parse_from(["speedtype", "--punctuation"]);
+        cli.apply(&mut options);
+        assert!(cli.has_overrides());
+        assert!(options.punctuation && !options.numbers);
+
+        let cli = Cli::parse_from(["speedtype", "--seed", "12"]);
+        cli.apply(&mut options);
+        assert_eq!(options.seed, Some(12));
+        let cli = Cli::parse_from(["speedtype", "--daily"]);
+        cli.apply(&mut options);
+        assert!(options.mode == GameMode::Daily);
+
+        assert!(Cli::try_parse_from(["speedtype", "--json-result", "--host"]).is_err());
+        let cli = Cli::parse_from(["speedtype", "export", "--mode", "time", "--profile", "ann"]);
+        assert!(matches!(cli.command, Some(Command::Export(_))));
+        assert_eq!(cli.profile, Some("ann".to_string()));
+        assert!(!cli.has_overrides());
+
+        assert!(Cli::try_parse_from(["speedtype", "--time", "0"]).is_err());
+        assert!(!Cli::parse_from(["speedtype"]).has_overrides());
+    }
+}
@@ -0,0 +1,118 @@
+use std::io;
+
+use rand::{seq::SliceRandom, thread_rng};
+
+use crate::{
+    assets::{list_assets, read_asset, CODE},
+    langs::{text_length, WordSupplier},
+};
+
+pub const TAB_WIDTH: usize = 4;
+
+pub struct Snippet {
+    pub lines: Vec<String>,
+    pub source: String,
+}
+
+pub fn available_code_languages() -> Vec<String> {
+    list_assets(CODE)
+}
+
+/// Code files hold snippets, each starting with a `%% name` line. Tabs are
+/// expanded, trailing whitespace and surrounding blank lines are dropped.
+pub fn parse_snippets(lang: &str, buf: &str) -> Vec<Snippet> {
+    let mut snippets = vec![];
+    let mut current: Option<Snippet> = None;
+    for line in buf.lines() {
+        match line.strip_prefix("%%") {
+            Some(name) => {
+                snippets.extend(current.take());
+                current = Some(Snippet {
+                    lines: vec![],
+                    source: format!("{}: {}", lang, name.trim()),
+                });
+            }
+            None => {
+                if let Some(snippet) = &mut current {
+                    let line = line.replace('\t', &" ".repeat(TAB_WIDTH));
+                    snippet.lines.push(line.trim_end().to_string());
+                }
+            }
+        }
+    }
+    snippets.extend(current);
+    for snippet in &mut snippets {
+        while snippet.lines.last().is_some_and(|l| l.is_empty()) {
+            snippet.lines.pop();
+        }
+        let blank = snippet.lines.iter().take_while(|l| l.is_empty()).count();
+        snippet.lines.drain(..blank);
+    }
+    snippets.retain(|snippet| !snippet.lines.is_empty());
+    snippets
+}
+
+pub fn random_snippet(lang: &str) -> io::Result<Snippet> {
+    let buf = read_asset(CODE, lang).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("code snippets for `{}` not found", lang),
+        ),
+        _ => e,
+    })?;
+    let mut snippets = parse_snippets(lang, &buf.replace("\r\n", "\n"));
+    snippets.shuffle(&mut thread_rng());
+    snippets.pop().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no code snippets for `{}`", lang),
+        )
+    })
+}
+
+/// Lines of a snippet, typed with their indentation and ended with Enter.
+pub struct WordSupplierCode {
+    lines: std::vec::IntoIter<String>,
+    length: usize,
+}
+
+impl WordSupplierCode {
+    pub fn new(snippet: &Snippet) -> Self {
+        WordSupplierCode {
+            lines: snippet.lines.clone().into_iter(),
+            length: text_length(&snippet.lines),
+        }
+    }
+    /// Number of graphemes to type, counting line breaks.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
+
+impl WordSupplier for WordSupplierCode {
+    fn get_word(&mut self) -> Option<String> {
+        self.lines.next()
+    }
+    fn line_based(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_snippet_keeps_indentation() {
+        let snippets = parse_snippets(
+            "rust",
+            "ignored\n%% one\n\nfn a() {\n\tb();  \n}\n\n%%two\n\n",
+        );
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].lines, ["fn a() {", "    b();", "}"]);
+        assert_eq!(snippets[0].source, "rust: one");
+        for lang in available_code_languages() {
+            assert!(random_snippet(&lang).is_ok());
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+
+use crate::{
+    app::{GameMode, GameOptions},
+    profile::Profile,
+};
+
+pub fn config_path(profile: &Profile) -> io::Result<PathBuf> {
+    Ok(profile.config_dir()?.join("config.toml"))
+}
+
+/// Options file as read from disk. Keys this version doesn't know about are
+/// kept in `table` so saving the options doesn't drop them.
+pub struct Config {
+    table: Table,
+}
+
+fn read_key<T: DeserializeOwned>(
+    table: &Table,
+    key: &str,
+    target: &mut T,
+    valid: impl Fn(&T) -> bool,
+    errors: &mut Vec<String>,
+) {
+    if let Some(value) = table.get(key) {
+        match value.clone().try_into::<T>() {
+            Ok(value) if valid(&value) => *target = value,
+            _ => errors.push(format!("config: invalid value for `{}`: {}", key, value)),
+        }
+    }
+}
+
+impl Config {
+    /// Parses the config, falling back to defaults for every missing or
+    /// invalid value. Problems are returned as messages instead of failing.
+    pub fn parse(buf: &str) -> (Config, GameOptions, Vec<String>) {
+        let mut options = GameOptions::default();
+        let mut errors = vec![];
+        let table = match buf.parse::<Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                errors.push(format!("config: {}", e.message()));
+                Table::new()
+            }
+        };
+        let mode = &mut options.mode;
+        read_key(&table, "mode", mode, |&m| m != GameMode::File, &mut errors);
+        read_key(&table, "time", &mut options.time, |&t| t > 0, &mut errors);
+        read_key(&table, "words", &mut options.words, |&w| w > 0, &mut errors);
+        let quote_length = &mut options.quote_length;
+        read_key(&table, "quote_length", quote_length, |_| true, &mut errors);
+        let language = &mut options.language;
+        read_key(&table, "language", language, |l| !l.is_empty(), &mut errors);
+        read_key(&table, "theme", &mut options.theme, |_| true, &mut errors);
+        let live_stats = &mut options.live_stats;
+        read_key(&table, "live_stats", live_stats, |_| true, &mut errors);
+        let allow_backspace = &mut options.allow_backspace;
+        read_key(
+            &table,
+            "allow_backspace",
+            allow_backspace,
+            |_| true,
+            &mut errors,
+        );
+        let punctuation = &mut options.punctuation;
+        read_key(&table, "punctuation", punctuation, |_| true, &mut errors);
+        let numbers = &mut options.numbers;
+        read_key(&table, "numbers", numbers, |_| true, &mut errors);
+        let code_language = &mut options.code_language;
+        read_key(
+            &table,
+            "code_language",
+            code_language,
+            |l| !l.is_empty(),
+            &mut errors,
+        );
+        let skip_indent = &mut options.skip_indent;
+        read_key(&table, "skip_indent", skip_indent, |_| true, &mut errors);
+        read_key(&table, "ghost", &mut options.ghost, |_| true, &mut errors);
+        let ghost_wpm = &mut options.ghost_wpm;
+        read_key(&table, "ghost_wpm", ghost_wpm, |&w| w > 0, &mut errors);
+        read_key(&table, "seed", &mut options.seed, |_| true, &mut errors);
+        (Config { table }, options, errors)
+    }
+
+    pub fn load_from(path: &Path) -> (Config, GameOptions, Vec<String>) {
+        match fs::read_to_string(path) {
+            Ok(buf) => Config::parse(&buf),
+            Err(e) => {
+                let (config, options, mut errors) = Config::parse("");
+                if e.kind() != io::ErrorKind::NotFound {
+                    errors.push(format!("config: {}", e));
+                }
+                (config, options, errors)
+            }
+        }
+    }
+
+    pub fn load(profile: &Profile) -> (Config, GameOptions, Vec<String>) {
+        match config_path(profile) {
+            Ok(path) => Config::load_from(&path),
+            Err(e) => {
+                let (config, options, mut errors) = Config::parse("");
+                errors.push(format!("config: {}", e));
+                (config, options, errors)
+            }
+        }
+    }
+
+    pub fn to_toml(&mut self, options: &GameOptions) -> String {
+        let known = [
+            ("mode", Value::try_from(options.mode)),
+            ("time", Value::try_from(options.time)),
+            ("words", Value::try_from(options.words)),
+            ("quote_length", Value::try_from(options.quote_length)),
+            ("language", Value::try_from(&options.language)),
+            ("theme", Value::try_from(options.theme)),
+            ("live_stats", Value::try_from(options.live_stats)),
+            ("allow_backspace", Value::try_from(options.allow_backspace)),
+            ("punctuation", Value::try_from(options.punctuation)),
+            ("numbers", Value::try_from(options.numbers)),
+            ("code_language", Value::try_from(&options.code_language)),
+            ("skip_indent", Value::try_from(options.skip_indent)),
+            ("ghost", Value::try_from(options.ghost)),
+            ("ghost_wpm", Value::try_from(options.ghost_wpm)),
+            ("seed", Value::try_from(options.seed)),
+        ];
+        for (key, value) in known {
+            if let Ok(value) = value {
+                self.table.insert(key.to_string(), value);
+            }
+        }
+        self.table.to_string()
+    }
+
+    pub fn save(&mut self, options: &GameOptions, profile: &Profile) -> io::Result<()> {
+        let path = config_path(profile)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_toml(options))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_values_keep_defaults() {
+        let (_, options, errors) =
+            Config::parse("mode = \"words\"\ntime = -5\nwords = 50\ntheme = \"neon\"\n");
+        assert!(options.mode == GameMode::Words);
+        assert_eq!(options.words, 50);
+        assert_eq!(options.time, GameOptions::default().time);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn unknown_keys_survive_saving() {
+        let (mut config, mut options, errors) =
+            Config::parse("future_option = [1, 2]\ntime = 30\n");
+        assert!(errors.is_empty());
+        options.time = 10;
+        let saved = config.to_toml(&options);
+        let (_, options, errors) = Config::parse(&saved);
+        assert!(errors.is_empty());
+        assert_eq!(options.time, 10);
+        assert!(saved.contains("future_option = [1, 2]"));
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ratatui::crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{engine::GameStats, history::append_line, input::read_key_block};
+
+/// The daily challenge is always this many words of the bundled list, without
+/// punctuation or numbers, so everyone types the same text.
+pub const DAILY_WORDS: u32 = 50;
+pub const DAILY_LANGUAGE: &str = "english";
+
+/// Days since the Unix epoch, in UTC.
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86400)
+        .unwrap_or_default()
+}
+
+pub fn daily_seed(day: u64) -> u64 {
+    day.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+/// `day` as a calendar date, `YYYY-MM-DD`.
+pub fn date(day: u64) -> String {
+    // Civil from days, by Howard Hinnant.
+    let z = day as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + (m <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Day of a `YYYY-MM-DD` date, the inverse of `date`.
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    // Days from civil, by Howard Hinnant.
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if m > 2 { m - 3 } else { m + 9 };
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    u64::try_from(era * 146097 + doe - 719468).ok()
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DailyResult {
+    pub day: u64,
+    pub profile: String,
+    /// As in [`GameStats::timestamp`].
+    pub timestamp: u64,
+    pub wpm: f64,
+    pub acc: f64,
+    /// Duration of the test in seconds.
+    pub time: f64,
+    /// Left before the end. The attempt still counts, with no score.
+    #[serde(default)]
+    pub aborted: bool,
+}
+
+pub fn daily_path() -> io::Result<PathBuf> {
+    let mut path = dirs::data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no user data directory"))?;
+    path.push("speedtype");
+    path.push("daily.jsonl");
+    Ok(path)
+}
+
+/// Daily results of every profile, one JSON object per line like the history.
+/// Unlike the history they are shared, to rank the profiles against each other.
+pub fn load_daily() -> io::Result<Vec<DailyResult>> {
+    let mut buf = String::new();
+    match File::open(daily_path()?) {
+        Ok(mut file) => file.read_to_string(&mut buf)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    Ok(latest_attempts(
+        buf.lines()
+            .filter_map(|line| serde_json::from_str(line).ok()),
+    ))
+}
+
+/// Last line of every profile and day: the finished result replaces the
+/// line written when the attempt began.
+fn latest_attempts(lines: impl Iterator<Item = DailyResult>) -> Vec<DailyResult> {
+    let mut results: Vec<DailyResult> = vec![];
+    for line in lines {
+        match results
+            .iter_mut()
+            .find(|r| r.day == line.day && r.profile == line.profile)
+        {
+            Some(result) => *result = line,
+            None => results.push(line),
+        }
+    }
+    results
+}
+
+/// Uses up today's attempt of `profile` as the test starts, so leaving it
+/// early doesn't allow another try. Returns the day to score the attempt on,
+/// unless it was already used.
+pub fn begin_daily(profile: &str) -> io::Result<Option<u64>> {
+    let day = today();
+    if load_daily()?
+        .iter()
+        .any(|r| r.day == day && r.profile == profile)
+    {
+        return Ok(None);
+    }
+    let attempt = DailyResult {
+        day,
+        profile: profile.to_string(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        wpm: 0.0,
+        acc: 0.0,
+        time: 0.0,
+        aborted: true,
+    };
+    append_line(daily_path()?, &attempt)?;
+    Ok(Some(day))
+}
+
+/// Scores the attempt begun with `begin_daily` on `day`, even if it ended
+/// after midnight.
+pub fn record_daily(stats: &GameStats, profile: &str, day: u64) -> io::Result<()> {
+    let result = DailyResult {
+        day,
+        profile: profile.to_string(),
+        timestamp: stats.timestamp,
+        wpm: stats.wpm,
+        acc: stats.acc,
+        time: stats.time.as_secs_f64(),
+        aborted: false,
+    };
+    append_line(daily_path()?, &result)
+}
+
+/// Finished results of `day`, fastest first.
+pub fn leaderboard(results: &[DailyResult], day: u64) -> Vec<&DailyResult> {
+    let mut ranking = results
+        .iter()
+        .filter(|r| r.day == day && !r.aborted)
+        .collect::<Vec<_>>();
+    ranking.sort_by(|a, b| b.wpm.total_cmp(&a.wpm));
+    ranking
+}
+
+/// Consecutive days `profile` completed the challenge up to `today`, and
+/// whether today is one of them. A streak still counts before today's
+/// attempt.
+pub fn streak(results: &[DailyResult], profile: &str, today: u64) -> (u32, bool) {
+    let days = results
+        .iter()
+        .filter(|r| r.profile == profile && !r.aborted)
+        .map(|r| r.day)
+        .collect::<HashSet<_>>();
+    let done_today = days.contains(&today);
+    let mut day = today + done_today as u64;
+    let mut count = 0;
+    while let Some(prev) = day.checked_sub(1).filter(|d| days.contains(d)) {
+        count += 1;
+        day = prev;
+    }
+    (count, done_today)
+}
+
+pub enum LeaderboardAction {
+    Continue,
+    Back,
+}
+
+pub struct LeaderboardScreen {
+    results: io::Result<Vec<DailyResult>>,
+    day: u64,
+    today: u64,
+}
+
+impl LeaderboardScreen {
+    pub fn new() -> Self {
+        LeaderboardScreen {
+            results: load_daily(),
+            day: today(),
+            today: today(),
+        }
+    }
+    pub fn handle_events(&mut self) -> io::Result<LeaderboardAction> {
+        match read_key_block()? {
+            KeyCode::Esc => return Ok(LeaderboardAction::Back),
+            KeyCode::Left => self.day = self.day.saturating_sub(1),
+            KeyCode::Right => self.day = (self.day + 1).min(self.today),
+            _ => {}
+        }
+        Ok(LeaderboardAction::Continue)
+    }
+}
+
+impl Default for LeaderboardScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+mod widget {
+    use ratatui::{prelude::*, widgets::*};
+
+    use super::{date, leaderboard, streak, LeaderboardScreen};
+
+    impl Widget for &LeaderboardScreen {
+        fn render(self, area: Rect, buf: &mut Buffer) {
+            use Constraint::*;
+            let [top, _, table_area] =
+                Layout::vertical([Length(1), Length(1), Fill(1)]).areas(area);
+            Line::raw(format!(
+                "Daily challenge {} - Left/Right change the day, Esc returns",
+                date(self.day)
+            ))
+            .bold()
+            .centered()
+            .render(top, buf);
+            let table_area =
+                Layout::horizontal([Fill(1), Length(60), Fill(1)]).areas::<3>(table_area)[1];
+            let results = match &self.results {
+                Ok(results) => results,
+                Err(e) => {
+                    Line::raw(format!("Could not read daily results: {}", e))
+                        .red()
+                        .centered()
+                        .render(table_area, buf);
+                    return;
+                }
+            };
+            let ranking = leaderboard(results, self.day);
+            if ranking.is_empty() {
+                Line::raw("Nobody has finished this day")
+                    .centered()
+                    .render(table_area, buf);
+                return;
+            }
+            let rows = ranking.iter().enumerate().map(|(i, r)| {
+                let (days, _) = streak(results, &r.profile, self.day);
+                Row::new(vec![
+                    format!("{}.", i + 1),
+                    r.profile.clone(),
+                    format!("{:.02}", r.wpm),
+                    format!("{:.02}", r.acc),
+                    format!("{:.1} s", r.time),
+                    days.to_string(),
+                ])
+            });
+            let table = Table::new(
+                rows,
+                [
+                    Length(4),
+                    Fill(1),
+                    Length(7),
+                    Length(7),
+                    Length(8),
+                    Length(7),
+                ],
+            )
+            .header(Row::new(vec!["", "Profile", "WPM", "Acc", "Time", "Streak"]).bold())
+            .block(Block::bordered().title("Leaderboard"));
+            Widget::render(table, table_area, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn result(day: u64, profile: &str, wpm: f64) -> DailyResult {
+        DailyResult {
+            day,
+            profile: profile.to_string(),
+            timestamp: 0,
+            wpm,
+            acc: 100.0,
+            time: 30.0,
+            aborted: false,
+        }
+    }
+
+    #[test]
+    fn streaks_count_consecutive_days() {
+        let aborted = DailyResult {
+            aborted: true,
+            ..result(13, "a", 0.0)
+        };
+        let results = [
+            result(10, "a", 50.0),
+            result(12, "a", 60.0),
+            result(13, "a", 55.0),
+            result(13, "b", 70.0),
+            DailyResult {
+                profile: "c".to_string(),
+                ..aborted.clone()
+            },
+            DailyResult {
+                day: 11,
+                ..aborted.clone()
+            },
+        ];
+        assert_eq!(streak(&results, "a", 13), (2, true));
+        assert_eq!(streak(&results, "a", 14), (2, false));
+        assert_eq!(streak(&results, "a", 15), (0, false));
+        assert_eq!(streak(&results, "c", 13), (0, false));
+        assert_eq!(leaderboard(&results, 13).len(), 2);
+        assert_eq!(leaderboard(&results, 13)[0].profile, "b");
+        let attempts = latest_attempts([aborted.clone(), result(13, "b", 40.0)].into_iter());
+        assert!(attempts[0].aborted);
+        let attempts = latest_attempts([aborted, result(13, "a", 40.0)].into_iter());
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].wpm, 40.0);
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(20743), "2026-10-17");
+        assert_eq!(parse_date("2026-10-17"), Some(20743));
+        assert_eq!(parse_date("2026-13-01"), None);
+    }
+}
@@ -0,0 +1,319 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    ghost::Ghost,
+    keys::KeyStats,
+    replay::{KeyLog, KeyRecorder, Keystroke},
+    text::{CharBreakdown, TextManagerDyn},
+    timer::{consistency, wpm_from_letters, Sample, Sampler},
+};
+
+/// What ends a test.
+pub enum Goal {
+    Time(Duration),
+    Words(usize),
+    /// Type a fixed text until it runs out.
+    Text {
+        source: String,
+        length: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EngineState {
+    /// Nothing typed yet, the clock starts with the first keystroke.
+    Waiting,
+    Running,
+    Finished,
+}
+
+/// Keystroke made at `time`, measured from any fixed start chosen by the
+/// client, the same for every event of a test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputEvent {
+    pub time: Duration,
+    pub key: Keystroke,
+}
+
+pub struct LiveStats {
+    /// Time since the first keystroke.
+    pub elapsed: Duration,
+    /// Speed as of the last full second.
+    pub wpm: f64,
+    pub accuracy: f64,
+    /// Progress toward the goal in percent.
+    pub progress: u16,
+    pub position: usize,
+    /// Position of the ghost, once the test is running.
+    pub ghost: Option<usize>,
+}
+
+pub struct GameStats {
+    /// Seconds since the Unix epoch at the moment the test ended.
+    pub timestamp: u64,
+    pub log: KeyLog,
+    pub wpm: f64,
+    pub raw_wpm: f64,
+    pub acc: f64,
+    pub consistency: f64,
+    pub chars: CharBreakdown,
+    pub correct: usize,
+    pub typed: usize,
+    pub time: Duration,
+    pub samples: Vec<Sample>,
+    pub source: Option<String>,
+    pub keys: KeyStats,
+    pub seed: Option<u64>,
+}
+
+/// A typing test driven only by the events fed to it, without a terminal or
+/// a clock of its own.
+pub struct Engine {
+    text_manager: TextManagerDyn,
+    goal: Goal,
+    allow_backspace: bool,
+    ghost: Option<Ghost>,
+    ghost_position: Option<usize>,
+    /// Seed of the generated words, kept with the result.
+    seed: Option<u64>,
+    state: EngineState,
+    start: Duration,
+    elapsed: Duration,
+    sampler: Sampler,
+    recorder: KeyRecorder,
+}
+
+impl Engine {
+    pub fn new(text_manager: TextManagerDyn, goal: Goal) -> Self {
+        Engine {
+            text_manager,
+            goal,
+            allow_backspace: true,
+            ghost: None,
+            ghost_position: None,
+            seed: None,
+            state: EngineState::Waiting,
+            start: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            sampler: Sampler::new(),
+            recorder: KeyRecorder::new(),
+        }
+    }
+    pub fn with_backspace(self, allow_backspace: bool) -> Self {
+        Engine {
+            allow_backspace,
+            ..self
+        }
+    }
+    pub fn with_ghost(self, ghost: Option<Ghost>) -> Self {
+        Engine { ghost, ..self }
+    }
+    pub fn with_seed(self, seed: Option<u64>) -> Self {
+        Engine { seed, ..self }
+    }
+    pub fn state(&self) -> EngineState {
+        self.state
+    }
+    /// The text being typed, to show it. Keystrokes go through `handle`.
+    pub fn text_manager_mut(&mut self) -> &mut TextManagerDyn {
+        &mut self.text_manager
+    }
+    /// Starts the clock at `time` without a keystroke, as races do.
+    pub fn start(&mut self, time: Duration) {
+        if self.state == EngineState::Waiting {
+            self.state = EngineState::Running;
+            self.start = time;
+        }
+    }
+    /// Advances the clock to `time`, which ends a timed test once the time is
+    /// up.
+    pub fn tick(&mut self, time: Duration) {
+        if self.state != EngineState::Running {
+            return;
+        }
+        self.elapsed = time.saturating_sub(self.start).max(self.elapsed);
+        self.sampler.update(
+            self.elapsed,
+            self.text_manager.correct(),
+            self.text_manager.keystrokes(),
+            self.text_manager.errors(),
+        );
+        if let Some(ghost) = &mut self.ghost {
+            self.ghost_position = Some(ghost.position(self.elapsed));
+        }
+        self.check_finished();
+    }
+    pub fn handle(&mut self, event: InputEvent) {
+        if self.state == EngineState::Waiting {
+            match event.key {
+                Keystroke::Char(c) if c != '\n' => self.start(event.time),
+                _ => return,
+            }
+        }
+        self.tick(event.time);
+        if self.state != EngineState::Running || event.key.is_deletion() && !self.allow_backspace {
+            return;
+        }
+        self.text_manager.set_time(self.elapsed);
+        event.key.apply(&mut self.text_manager);
+        self.recorder.record(self.elapsed, event.key);
+        self.check_finished();
+    }
+    fn check_finished(&mut self) {
+        // A text replayed from a ghost can run out before the goal is reached.
+        let finished = self.text_manager.text_finished()
+            || match self.goal {
+                Goal::Time(limit) => self.elapsed >= limit,
+                Goal::Words(words) => self.text_manager.words_completed() >= words,
+                Goal::Text { .. } => false,
+            };
+        if finished {
+            self.state = EngineState::Finished;
+        }
+    }
+    /// Time the result is computed over: the time limit, if there is one,
+    /// unless the text ran out before it.
+    fn duration(&self) -> Duration {
+        match self.goal {
+            Goal::Time(limit) => limit.min(self.elapsed),
+            _ => self.elapsed,
+        }
+    }
+    fn progress(&mut self) -> u16 {
+        let percent = match self.goal {
+            Goal::Time(limit) => self.elapsed.as_millis() * 100 / limit.as_millis().max(1),
+            Goal::Words(words) => {
+                (self.text_manager.words_completed() * 100 / words.max(1)) as u128
+            }
+            Goal::Text { length, .. } => {
+                (self.text_manager.position() * 100 / length.max(1)) as u128
+            }
+        };
+        percent.min(100) as u16
+    }
+    pub fn live_stats(&mut self) -> LiveStats {
+        LiveStats {
+            elapsed: self.elapsed,
+            wpm: self.sampler.samples().last().map_or(0f64, |s| s.wpm),
+            accuracy: self.text_manager.accuracy(),
+            progress: self.progress(),
+            position: self.text_manager.position(),
+            ghost: self.ghost_position,
+        }
+    }
+    /// The result, once the test is finished.
+    pub fn result(&mut self) -> Option<GameStats> {
+        if self.state != EngineState::Finished {
+            return None;
+        }
+        let time = self.duration();
+        let samples = self.sampler.finish(
+            self.elapsed.min(time),
+            self.text_manager.correct(),
+            self.text_manager.keystrokes(),
+            self.text_manager.errors(),
+        );
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Some(GameStats {
+            timestamp,
+            log: self.recorder.finish(&self.text_manager, timestamp),
+            wpm: wpm_from_letters(self.text_manager.correct(), time),
+            raw_wpm: wpm_from_letters(self.text_manager.keystrokes(), time),
+            acc: self.text_manager.accuracy(),
+            consistency: consistency(&samples),
+            chars: self.text_manager.breakdown(),
+            correct: self.text_manager.correct(),
+            typed: self.text_manager.keystrokes(),
+            time,
+            samples,
+            source: match &self.goal {
+                Goal::Text { source, .. } => Some(source.clone()),
+                _ => None,
+            },
+            keys: self.text_manager.key_stats().clone(),
+            seed: self.seed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::langs::{WordSupplier, WordSupplierBasic};
+
+    fn engine(text: &str, goal: Goal) -> Engine {
+        let words: Box<dyn WordSupplier> = Box::new(WordSupplierBasic::new(text));
+        Engine::new(TextManagerDyn::new(words), goal)
+    }
+
+    fn type_text(engine: &mut Engine, text: &str, start_ms: u64) {
+        for (i, c) in text.chars().enumerate() {
+            engine.handle(InputEvent {
+                time: Duration::from_millis(start_ms + i as u64 * 500),
+                key: Keystroke::Char(c),
+            });
+        }
+    }
+
+    #[test]
+    fn engine_runs_on_events() {
+        let mut words = engine("ab cd ef", Goal::Words(2));
+        words.handle(InputEvent {
+            time: Duration::from_secs(1),
+            key: Keystroke::Backspace,
+        });
+        assert_eq!(words.state(), EngineState::Waiting);
+        type_text(&mut words, "ab x", 2000);
+        assert_eq!(words.state(), EngineState::Running);
+        assert_eq!(words.live_stats().elapsed, Duration::from_millis(1500));
+        assert!(words.result().is_none());
+        words.handle(InputEvent {
+            time: Duration::from_millis(4000),
+            key: Keystroke::Backspace,
+        });
+        type_text(&mut words, "cd", 4500);
+        assert_eq!(words.state(), EngineState::Finished);
+        let stats = words.result().unwrap();
+        assert_eq!(stats.time, Duration::from_secs(3));
+        assert_eq!(stats.wpm, 20f64);
+        // Raw speed counts the mistyped key too, like the samples.
+        assert_eq!(stats.raw_wpm, 24f64);
+        assert_eq!(stats.chars.corrected, 1);
+        assert_eq!(stats.log.keys.len(), 7);
+        assert_eq!(stats.log.keys[6].ms, 3000);
+
+        let mut timed = engine("ab cd ef", Goal::Time(Duration::from_secs(2)));
+        type_text(&mut timed, "ab", 0);
+        timed.tick(Duration::from_millis(1500));
+        assert_eq!(timed.live_stats().progress, 75);
+        assert_eq!(timed.live_stats().wpm, 16f64);
+        timed.tick(Duration::from_secs(2));
+        let stats = timed.result().unwrap();
+        assert_eq!(stats.time, Duration::from_secs(2));
+        assert_eq!(stats.wpm, 12f64);
+
+        // A personal best replayed as the text can be shorter than the limit.
+        let mut short = engine("ab", Goal::Time(Duration::from_secs(60)));
+        type_text(&mut short, "ab", 0);
+        let stats = short.result().unwrap();
+        assert_eq!(stats.time, Duration::from_millis(500));
+        assert_eq!(stats.wpm, 48f64);
+    }
+
+    #[test]
+    fn text_goal_finishes_without_rendering() {
+        let goal = Goal::Text {
+            source: "test".to_string(),
+            length: 5,
+        };
+        let mut text = engine("ab cd", goal);
+        type_text(&mut text, "ab c", 0);
+        assert_eq!(text.state(), EngineState::Running);
+        type_text(&mut text, "d", 2000);
+        assert_eq!(text.state(), EngineState::Finished);
+        assert_eq!(text.result().unwrap().source.as_deref(), Some("test"));
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use clap::{Args, ValueEnum};
+
+use crate::{
+    app::GameMode,
+    daily::{date, parse_date, today},
+    history::{load_history, GameResult},
+    profile::Profile,
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+fn parse_day(value: &str) -> Result<u64, String> {
+    parse_date(value).ok_or_else(|| format!("`{}` is not a YYYY-MM-DD date", value))
+}
+
+/// Which results to export. Dates are days in UTC, like the daily challenge.
+#[derive(Args, Default)]
+pub struct ExportFilter {
+    /// Only results from this day on, as YYYY-MM-DD
+    #[arg(long, value_name = "DATE", value_parser = parse_day)]
+    pub since: Option<u64>,
+    /// Only results up to this day, as YYYY-MM-DD
+    #[arg(long, value_name = "DATE", value_parser = parse_day)]
+    pub until: Option<u64>,
+    /// Only results of this kind of test
+    #[arg(long, value_enum)]
+    pub mode: Option<GameMode>,
+    /// Only results typed from this word list
+    #[arg(long)]
+    pub lang: Option<String>,
+}
+
+impl ExportFilter {
+    pub fn matches(&self, result: &GameResult) -> bool {
+        let day = result.timestamp / 86400;
+        self.since.is_none_or(|since| day >= since)
+            && self.until.is_none_or(|until| day <= until)
+            && self.mode.is_none_or(|mode| result.mode.game_mode() == mode)
+            && self
+                .lang
+                .as_ref()
+                .is_none_or(|lang| &result.language == lang)
+    }
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Format of the exported results
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ExportFormat,
+    /// File to write, standard output if omitted
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+    #[command(flatten)]
+    pub filter: ExportFilter,
+}
+
+const CSV_HEADER: &str = "timestamp,date,mode,setting,language,punctuation,numbers,\
+duration,wpm,raw_wpm,acc,consistency,correct,typed,incorrect,extra,missed,corrected,seed";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One line per result. The WPM samples are left out, they are only in JSON.
+pub fn to_csv(results: &[&GameResult]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for r in results {
+        let fields = [
+            r.timestamp.to_string(),
+            date(r.timestamp / 86400),
+            r.mode.name().to_string(),
+            csv_field(&r.mode.setting()),
+            csv_field(&r.language),
+            r.punctuation.to_string(),
+            r.numbers.to_string(),
+            format!("{:.3}", r.duration),
+            format!("{:.2}", r.wpm),
+            format!("{:.2}", r.raw_wpm),
+            format!("{:.2}", r.acc),
+            format!("{:.2}", r.consistency),
+            r.correct.to_string(),
+            r.typed.to_string(),
+            r.chars.incorrect.to_string(),
+            r.chars.extra.to_string(),
+            r.chars.missed.to_string(),
+            r.chars.corrected.to_string(),
+            r.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Results of `history` that pass `filter`, oldest first.
+pub fn export(
+    history: &[GameResult],
+    filter: &ExportFilter,
+    format: ExportFormat,
+) -> io::Result<String> {
+    let results = history
+        .iter()
+        .filter(|r| filter.matches(r))
+        .collect::<Vec<_>>();
+    match format {
+        ExportFormat::Csv => Ok(to_csv(&results)),
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&results)? + "\n"),
+    }
+}
+
+/// Runs the `export` command, without opening the terminal interface.
+pub fn export_results(profile: &Profile, args: &ExportArgs) -> io::Result<()> {
+    let exported = export(&load_history(profile)?, &args.filter, args.format)?;
+    match &args.output {
+        Some(path) => fs::write(path, exported),
+        None => io::stdout().write_all(exported.as_bytes()),
+    }
+}
+
+/// Writes the whole history of `profile` to the working directory, named
+/// after the profile and the day.
+pub fn export_to_file(
+    profile: &Profile,
+    history: &[GameResult],
+    format: ExportFormat,
+) -> io::Result<PathBuf> {
+    let path = env::current_dir()?.join(format!(
+        "speedtype-{}-{}.{}",
+        profile.name(),
+        date(today()),
+        format.extension()
+    ));
+    fs::write(&path, export(history, &ExportFilter::default(), format)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history::ResultMode;
+
+    fn result(day: u64, mode: ResultMode) -> GameResult {
+        GameResult {
+            timestamp: day * 86400 + 3600,
+            seed: Some(3),
+            ..GameResult::sample(mode)
+        }
+    }
+
+    #[test]
+    fn exports_filtered_results() {
+        let history = [
+            result(20740, ResultMode::Time { time: 30 }),
+            result(
+                20742,
+                ResultMode::File {
+                    file: "a, \"b\".txt".to_string(),
+                },
+            ),
+            result(20743, ResultMode::Time { time: 60 }),
+        ];
+        let filter = ExportFilter {
+            since: parse_date("2026-10-15"),
+            ..Default::default()
+        };
+        let csv = export(&history, &filter, ExportFormat::Csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].starts_with("1792112400,2026-10-16,file,\"a, \"\"b\"\".txt\",english,"));
+        assert!(lines[2].ends_with(",3"));
+
+        let filter = ExportFilter {
+            mode: Some(GameMode::Time),
+            until: parse_date("2026-10-16"),
+            ..Default::default()
+        };
+        let json = export(&history, &filter, ExportFormat::Json).unwrap();
+        let results: Vec<GameResult> = serde_json::from_str(&json).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].timestamp, history[0].timestamp);
+    }
+}
@@ -1,135 +1,274 @@
-use ratatui::crossterm::event::KeyCode;
-use std::time::Duration;
+use rand::{thread_rng, Rng};
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use std::{
+    iter,
+    path::Path,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    input::read_key,
-    langs::WordSupplierRandomized,
-    text::TextManagerLang,
-    timer::{wpm_from_letters, TimeManager},
+    app::{GameMode, GameOptions},
+    code::{random_snippet, WordSupplierCode},
+    daily::{daily_seed, today, DAILY_LANGUAGE, DAILY_WORDS},
+    engine::{Engine, EngineState, GameStats, Goal, InputEvent},
+    ghost::{Ghost, GhostMode},
+    history::{generated_text, load_history},
+    input::{keystroke, read_key_event},
+    keys::load_key_stats,
+    langs::{
+        WordSupplier, WordSupplierAdaptive, WordSupplierBasic, WordSupplierProse,
+        WordSupplierRandomized,
+    },
+    layout::Theme,
+    profile::Profile,
+    quotes::{quote_language, random_quote},
+    race::{Race, RaceText},
+    stats::best_replay,
+    text::TextManagerDyn,
 };
 
-enum GameAction {
-    Reset,
-    Continue,
-    Quit,
-    End(GameStats),
+fn generated_words(
+    options: &GameOptions,
+    seed: u64,
+) -> std::io::Result<WordSupplierProse<WordSupplierRandomized>> {
+    Ok(WordSupplierProse::new(
+        WordSupplierRandomized::new(&options.language, seed)?,
+        options.punctuation,
+        options.numbers,
+        seed,
+    ))
 }
 
-struct StartedGame {
-    time_manager: TimeManager,
-    text_manager: TextManagerLang,
+/// The seed given in the options, or a fresh one. Daily challenges use the
+/// seed of the day.
+fn seed(options: &GameOptions) -> u64 {
+    if options.mode == GameMode::Daily {
+        return daily_seed(today());
+    }
+    options.seed.unwrap_or_else(|| thread_rng().gen())
 }
 
-impl StartedGame {
-    fn new(text_manager: TextManagerLang, duration: Duration) -> Self {
-        StartedGame {
-            time_manager: TimeManager::new(duration),
-            text_manager,
+fn word_source(
+    options: &GameOptions,
+    profile: &Profile,
+    seed: u64,
+) -> std::io::Result<(Box<dyn WordSupplier>, Goal)> {
+    Ok(match options.mode {
+        GameMode::Time => (
+            Box::new(generated_words(options, seed)?),
+            Goal::Time(Duration::from_secs(options.time as u64)),
+        ),
+        GameMode::Words => (
+            Box::new(generated_words(options, seed)?),
+            Goal::Words(options.words as usize),
+        ),
+        GameMode::Quote => {
+            let quote = random_quote(quote_language(&options.language), options.quote_length)?;
+            let goal = Goal::Text {
+                length: quote.length(),
+                source: quote.source,
+            };
+            (Box::new(WordSupplierBasic::new(&quote.text)), goal)
         }
-    }
-    fn handle_events(&mut self) -> std::io::Result<GameAction> {
-        if self.time_manager.time_expired() {
-            return Ok(GameAction::End(GameStats {
-                wpm: wpm_from_letters(self.text_manager.correct(), self.time_manager.duration()),
-                acc: self.text_manager.accuracy(),
-            }));
+        GameMode::Practice => (
+            Box::new(WordSupplierAdaptive::new(
+                &options.language,
+                load_key_stats(profile)?,
+                seed,
+            )?),
+            Goal::Words(options.words as usize),
+        ),
+        GameMode::Daily => {
+            let words = WordSupplierRandomized::bundled(DAILY_LANGUAGE, seed)?;
+            (
+                Box::new(WordSupplierProse::new(words, false, false, seed)),
+                Goal::Words(DAILY_WORDS as usize),
+            )
         }
-        let action = if let Some(key) = read_key()? {
-            match key {
-                KeyCode::Char(u) => {
-                    self.text_manager.handle_char(u);
-                    GameAction::Continue
-                }
-                KeyCode::Backspace => {
-                    self.text_manager.handle_backspace();
-                    GameAction::Continue
-                }
-                KeyCode::Esc => GameAction::Quit,
-                KeyCode::Tab => GameAction::Reset,
-                _ => GameAction::Continue,
-            }
-        } else {
-            GameAction::Continue
-        };
-        Ok(action)
-    }
-    fn wpm(&self) -> usize {
-        self.time_manager.wpm(self.text_manager.correct())
-    }
-    fn accuracy(&self) -> usize {
-        self.text_manager.accuracy().ceil() as usize
-    }
-    fn gauge_percent(&self) -> u16 {
-        self.time_manager.percent_elapsed()
-    }
+        GameMode::Code => {
+            let snippet = random_snippet(&options.code_language)?;
+            let lines = WordSupplierCode::new(&snippet);
+            let goal = Goal::Text {
+                length: lines.length(),
+                source: snippet.source,
+            };
+            (Box::new(lines), goal)
+        }
+        GameMode::File => {
+            let path = options.file.as_deref().unwrap_or(Path::new(""));
+            let words = WordSupplierBasic::from_file(path)?;
+            let goal = Goal::Text {
+                source: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                length: words.length(),
+            };
+            (Box::new(words), goal)
+        }
+    })
 }
 
-struct BeforeStartedGame {
-    text_manager: TextManagerLang,
-    duration: Duration,
+/// Text for a race: the whole text in modes with a fixed text, otherwise as
+/// many words as in words mode.
+pub fn race_text(options: &GameOptions, profile: &Profile) -> std::io::Result<RaceText> {
+    let (mut word_supplier, goal) = word_source(options, profile, seed(options))?;
+    let (limit, source) = match goal {
+        Goal::Text { source, .. } => (usize::MAX, Some(source)),
+        Goal::Words(words) => (words, None),
+        Goal::Time(_) => (options.words as usize, None),
+    };
+    Ok(RaceText {
+        line_based: word_supplier.line_based(),
+        words: iter::from_fn(|| word_supplier.get_word())
+            .take(limit)
+            .collect(),
+        skip_indent: options.skip_indent,
+        source,
+    })
 }
 
-enum GameState {
-    BeforeStart(BeforeStartedGame),
-    Started(StartedGame),
+/// Engine for a test with `options`.
+fn engine(options: &GameOptions, profile: &Profile) -> std::io::Result<Engine> {
+    if options.ghost == GhostMode::Best {
+        return against_best(options, profile);
+    }
+    let seed = seed(options);
+    let (word_supplier, goal) = word_source(options, profile, seed)?;
+    let mut text_manager = TextManagerDyn::new(word_supplier);
+    text_manager.set_skip_indent(options.skip_indent);
+    let ghost = (options.ghost == GhostMode::Pace).then(|| Ghost::pace(options.ghost_wpm));
+    Ok(Engine::new(text_manager, goal)
+        .with_backspace(options.allow_backspace)
+        .with_ghost(ghost)
+        .with_seed((generated_text(options) || options.mode == GameMode::Daily).then_some(seed)))
 }
 
-impl GameState {
-    fn new(duration: Duration) -> Self {
-        GameState::BeforeStart(BeforeStartedGame {
-            text_manager: TextManagerLang::new(WordSupplierRandomized::new("english").unwrap()),
-            duration,
-        })
-    }
+fn race_engine(race: &Race, options: &GameOptions) -> Engine {
+    let mut text_manager = TextManagerDyn::new(Box::new(race.text.word_supplier()));
+    text_manager.set_skip_indent(race.text.skip_indent);
+    let goal = Goal::Text {
+        source: race.text.source.clone().unwrap_or("race".to_string()),
+        length: race.text.length(),
+    };
+    Engine::new(text_manager, goal).with_backspace(options.allow_backspace)
 }
 
-pub struct LiveGame {
-    state: GameState,
+/// Retypes the text of the personal best while its replay races along. The
+/// result keeps the seed of the best, as the text is the same.
+fn against_best(options: &GameOptions, profile: &Profile) -> std::io::Result<Engine> {
+    let (log, seed) = best_replay(profile, &load_history(profile)?, options).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no saved replay of a personal best for these options",
+        )
+    })?;
+    let goal = match options.mode {
+        GameMode::Time => Goal::Time(Duration::from_secs(options.time as u64)),
+        GameMode::Words | GameMode::Practice => Goal::Words(options.words as usize),
+        GameMode::Daily => Goal::Words(DAILY_WORDS as usize),
+        GameMode::Quote | GameMode::Code | GameMode::File => Goal::Text {
+            source: "personal best".to_string(),
+            length: log.length(),
+        },
+    };
+    let mut text_manager = TextManagerDyn::new(Box::new(log.word_supplier()));
+    text_manager.set_skip_indent(log.skip_indent);
+    Ok(Engine::new(text_manager, goal)
+        .with_backspace(options.allow_backspace)
+        .with_ghost(Some(Ghost::replay(log)))
+        .with_seed(seed))
 }
 
-pub struct GameStats {
-    pub wpm: f64,
-    pub acc: f64,
+/// Test in the terminal: reads keys and hands them to the engine with their
+/// time.
+pub struct LiveGame {
+    engine: Engine,
+    start: Instant,
+    theme: Theme,
+    live_stats: bool,
+    race: Option<Box<Race>>,
 }
 
 pub enum NextState {
-    LiveGame(LiveGame),
-    GameEnded(GameStats),
+    LiveGame(Box<LiveGame>),
+    GameEnded(Box<GameStats>),
+    RaceEnded(Box<GameStats>, Box<Race>),
     Exit,
     Restart,
 }
 
 impl LiveGame {
-    pub fn new(duration: u32) -> Self {
+    pub fn new(options: &GameOptions, profile: &Profile) -> std::io::Result<Self> {
+        Ok(LiveGame {
+            engine: engine(options, profile)?,
+            start: Instant::now(),
+            theme: options.theme,
+            live_stats: options.live_stats,
+            race: None,
+        })
+    }
+    pub fn race(race: Box<Race>, options: &GameOptions) -> Self {
         LiveGame {
-            state: GameState::new(Duration::from_secs(duration as u64)),
+            engine: race_engine(&race, options),
+            start: Instant::now(),
+            theme: options.theme,
+            live_stats: options.live_stats,
+            race: Some(race),
+        }
+    }
+    /// Whether the first keystroke was made, or the race began.
+    pub fn started(&self) -> bool {
+        self.engine.state() != EngineState::Waiting
+    }
+    fn feed(&mut self, key: Option<KeyEvent>) {
+        let time = self.start.elapsed();
+        match key.and_then(keystroke) {
+            Some(key) => self.engine.handle(InputEvent { time, key }),
+            None => self.engine.tick(time),
+        }
+    }
+    /// Like `handle_events`, but the test starts with the countdown instead of
+    /// the first keystroke and the progress is shared with the other racers.
+    fn handle_race_events(mut self, mut race: Box<Race>) -> std::io::Result<NextState> {
+        race.poll();
+        let key = read_key_event()?;
+        if key.is_some_and(|key| key.code == KeyCode::Esc) {
+            return Ok(NextState::Exit);
+        }
+        if !race.started() {
+            return Ok(NextState::LiveGame(Box::new(LiveGame {
+                race: Some(race),
+                ..self
+            })));
+        }
+        self.engine.start(self.start.elapsed());
+        self.feed(key);
+        let position = self.engine.live_stats().position;
+        if let Some(game_stats) = self.engine.result() {
+            race.update(position, game_stats.wpm, Some(game_stats.time));
+            return Ok(NextState::RaceEnded(Box::new(game_stats), race));
         }
+        race.update(position, self.engine.live_stats().wpm.trunc(), None);
+        Ok(NextState::LiveGame(Box::new(LiveGame {
+            race: Some(race),
+            ..self
+        })))
     }
     pub fn handle_events(mut self) -> std::io::Result<NextState> {
-        let state = match self.state {
-            GameState::BeforeStart(mut game) => {
-                if let Some(key) = read_key()? {
-                    match key {
-                        KeyCode::Char(c) => {
-                            game.text_manager.handle_char(c);
-                            GameState::Started(StartedGame::new(game.text_manager, game.duration))
-                        }
-                        KeyCode::Esc => return Ok(NextState::Exit),
-                        KeyCode::Tab => return Ok(NextState::Restart),
-                        _ => GameState::BeforeStart(game),
-                    }
-                } else {
-                    GameState::BeforeStart(game)
-                }
-            }
-            GameState::Started(ref mut runner) => match runner.handle_events()? {
-                GameAction::Continue => self.state,
-                GameAction::Quit => return Ok(NextState::Exit),
-                GameAction::Reset => return Ok(NextState::Restart),
-                GameAction::End(game_stats) => return Ok(NextState::GameEnded(game_stats)),
-            },
-        };
-        Ok(NextState::LiveGame(LiveGame { state }))
+        if let Some(race) = self.race.take() {
+            return self.handle_race_events(race);
+        }
+        let key = read_key_event()?;
+        match key.map(|key| key.code) {
+            Some(KeyCode::Esc) => return Ok(NextState::Exit),
+            Some(KeyCode::Tab) => return Ok(NextState::Restart),
+            _ => self.feed(key),
+        }
+        match self.engine.result() {
+            Some(game_stats) => Ok(NextState::GameEnded(Box::new(game_stats))),
+            None => Ok(NextState::LiveGame(Box::new(self))),
+        }
     }
 }
 
@@ -137,12 +276,17 @@ mod widget {
     use ratatui::{
         buffer::Buffer,
         layout::Rect,
+        style::Stylize,
+        text::Line,
         widgets::{StatefulWidget, Widget},
     };
 
-    use crate::layout::{get_ui_live_widgets, AppLayout};
+    use crate::{
+        layout::{get_ui_live_widgets, AppLayout},
+        race::RaceProgress,
+    };
 
-    use super::{GameState, LiveGame};
+    use super::{EngineState, LiveGame};
 
     impl StatefulWidget for &mut LiveGame {
         type State = Option<(u16, u16)>;
@@ -153,19 +297,40 @@ mod widget {
                 text_area,
             } = AppLayout::new(area);
 
-            let text_manager = match &mut self.state {
-                GameState::BeforeStart(game) => &mut game.text_manager,
-                GameState::Started(started_game) => &mut started_game.text_manager,
-            };
-            text_manager.render(text_area, buf, state);
+            let stats = self.engine.live_stats();
+            self.engine
+                .text_manager_mut()
+                .view(self.theme)
+                .ghost(stats.ghost)
+                .render(text_area, buf, state);
 
-            if let GameState::Started(started_game) = &mut self.state {
-                let acc = started_game.accuracy();
-                let wpm = started_game.wpm();
-                let gauge_percent = started_game.gauge_percent();
-                let (gauge, stat_line) = get_ui_live_widgets(wpm, acc, gauge_percent);
+            if let Some(race) = &self.race {
+                // Racers go in the gap between the stats and the text.
+                let top = stat_area.bottom() + 1;
+                let racers = Rect {
+                    y: top,
+                    height: text_area.y.saturating_sub(top + 1),
+                    ..text_area
+                };
+                RaceProgress(race).render(racers, buf);
+                if let Some(countdown) = race.countdown().filter(|c| !c.is_zero()) {
+                    Line::raw(format!("Starting in {}", countdown.as_secs() + 1))
+                        .bold()
+                        .centered()
+                        .render(gauge_area, buf);
+                }
+            }
+            if self.engine.state() != EngineState::Waiting {
+                let acc = stats.accuracy.ceil() as usize;
+                let gap = stats
+                    .ghost
+                    .map(|ghost| stats.position as i64 - ghost as i64);
+                let (gauge, stat_line) =
+                    get_ui_live_widgets(stats.wpm as usize, acc, stats.progress, gap, self.theme);
                 gauge.render(gauge_area, buf);
-                stat_line.render(stat_area, buf);
+                if self.live_stats {
+                    stat_line.render(stat_area, buf);
+                }
             }
         }
     }
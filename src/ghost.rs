@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    replay::{KeyLog, WordSupplierList},
+    text::TextManager,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum GhostMode {
+    Off,
+    /// Advance at a fixed speed.
+    Pace,
+    /// Replay the personal best on the same text.
+    Best,
+}
+
+/// Second cursor racing the typist.
+pub enum Ghost {
+    Pace {
+        wpm: u32,
+    },
+    Replay {
+        log: KeyLog,
+        text_manager: Box<TextManager<WordSupplierList>>,
+        next: usize,
+    },
+}
+
+impl Ghost {
+    pub fn pace(wpm: u32) -> Self {
+        Ghost::Pace { wpm }
+    }
+    pub fn replay(log: KeyLog) -> Self {
+        Ghost::Replay {
+            text_manager: Box::new(log.text_manager()),
+            log,
+            next: 0,
+        }
+    }
+    /// Position of the ghost in the text `elapsed` after the first keystroke.
+    pub fn position(&mut self, elapsed: Duration) -> usize {
+        match self {
+            // One word is five characters, the same as when computing WPM.
+            Ghost::Pace { wpm } => (*wpm as f64 * 5f64 * elapsed.as_secs_f64() / 60f64) as usize,
+            Ghost::Replay {
+                log,
+                text_manager,
+                next,
+            } => {
+                while let Some(key) = log.keys.get(*next) {
+                    if Duration::from_millis(key.ms) > elapsed {
+                        break;
+                    }
+                    key.key.apply(text_manager);
+                    *next += 1;
+                }
+                text_manager.position()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::replay::{Keystroke, TimedKeystroke, REPLAY_VERSION};
+
+    #[test]
+    fn ghosts_advance_with_time() {
+        let mut pace = Ghost::pace(60);
+        assert_eq!(pace.position(Duration::from_secs(2)), 10);
+
+        let keys = "ab c".chars().enumerate().map(|(i, c)| TimedKeystroke {
+            ms: i as u64 * 1000,
+            key: Keystroke::Char(c),
+        });
+        let mut replay = Ghost::replay(KeyLog {
+            version: REPLAY_VERSION,
+            timestamp: 0,
+            words: vec!["ab".to_string(), "cd".to_string()],
+            line_based: false,
+            skip_indent: false,
+            keys: keys.collect(),
+        });
+        assert_eq!(replay.position(Duration::ZERO), 1);
+        assert_eq!(replay.position(Duration::from_millis(2500)), 3);
+        assert_eq!(replay.position(Duration::from_secs(10)), 4);
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use ratatui::{prelude::*, widgets::*};
+
+use crate::keys::{KeyStat, KeyStats};
+
+const ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl;", "zxcvbnm,./"];
+/// Miss rate drawn fully red.
+const WORST_MISS_RATE: f64 = 0.15;
+/// Latencies drawn fully green and fully red.
+const FAST: Duration = Duration::from_millis(120);
+const SLOW: Duration = Duration::from_millis(500);
+
+pub const HEATMAP_HEIGHT: u16 = 6;
+
+/// Statistics of a key on the keyboard, counting both lower and upper case.
+fn key_stat(stats: &KeyStats, key: char) -> KeyStat {
+    let mut stat = KeyStat::default();
+    for case in [key.to_string(), key.to_uppercase().to_string()] {
+        if let Some(s) = stats.keys.get(&case) {
+            stat.merge(s);
+        }
+    }
+    stat
+}
+
+/// Green for `badness` 0, through yellow, to red for 1.
+fn gradient(badness: f64) -> Color {
+    let t = badness.clamp(0f64, 1f64);
+    let red = (t * 2f64).min(1f64) * 200f64;
+    let green = ((1f64 - t) * 2f64).min(1f64) * 180f64;
+    Color::Rgb(red as u8 + 40, green as u8 + 40, 40)
+}
+
+fn show(key: &str) -> &str {
+    match key {
+        " " => "␣",
+        "\n" => "⏎",
+        key => key,
+    }
+}
+
+/// Two keyboards coloured by miss rate and by speed of every key, with the
+/// worst substitutions and slowest bigrams listed below.
+pub struct KeyboardHeatmap<'a> {
+    stats: &'a KeyStats,
+}
+
+impl<'a> KeyboardHeatmap<'a> {
+    pub fn new(stats: &'a KeyStats) -> Self {
+        KeyboardHeatmap { stats }
+    }
+
+    fn keyboard(&self, color: impl Fn(&KeyStat) -> Option<Color>) -> Text<'static> {
+        ROWS.iter()
+            .enumerate()
+            .map(|(offset, row)| {
+                let mut spans = vec![Span::raw(" ".repeat(offset))];
+                spans.extend(row.chars().map(|key| {
+                    let span = Span::raw(format!(" {} ", key));
+                    match color(&key_stat(self.stats, key)) {
+                        Some(color) => span.bg(color).black(),
+                        None => span.dark_gray(),
+                    }
+                }));
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    fn summary(&self) -> Line<'static> {
+        let mut missed = self
+            .stats
+            .keys
+            .iter()
+            .filter_map(|(key, stat)| Some((key, stat.top_substitution()?)))
+            .collect::<Vec<_>>();
+        missed.sort_by_key(|(_, (_, count))| std::cmp::Reverse(*count));
+        let missed = missed
+            .iter()
+            .take(3)
+            .map(|(key, (typed, count))| format!("{}→{} ×{}", show(key), show(typed), count))
+            .collect::<Vec<_>>();
+        let mut slow = self
+            .stats
+            .bigrams
+            .iter()
+            .filter_map(|(bigram, stat)| Some((bigram, stat.mean_latency()?)))
+            .collect::<Vec<_>>();
+        slow.sort_by_key(|(_, latency)| std::cmp::Reverse(*latency));
+        let slow = slow
+            .iter()
+            .take(3)
+            .map(|(bigram, latency)| format!("{} {} ms", bigram, latency.as_millis()))
+            .collect::<Vec<_>>();
+        let list = |items: Vec<String>| match items.is_empty() {
+            true => "-".to_string(),
+            false => items.join(", "),
+        };
+        Line::raw(format!(
+            "Missed: {}   Slowest pairs: {}",
+            list(missed),
+            list(slow)
+        ))
+    }
+}
+
+impl<'a> Widget for KeyboardHeatmap<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        use Constraint::*;
+        let [keyboards, summary] = Layout::vertical([Length(5), Length(1)]).areas(area);
+        let [_, errors, speed, _] =
+            Layout::horizontal([Fill(1), Length(34), Length(34), Fill(1)]).areas(keyboards);
+        Paragraph::new(self.keyboard(|stat| {
+            stat.miss_rate()
+                .map(|rate| gradient(rate / WORST_MISS_RATE))
+        }))
+        .block(Block::bordered().title("Misses"))
+        .render(errors, buf);
+        Paragraph::new(self.keyboard(|stat| {
+            stat.mean_latency().map(|latency| {
+                let t = latency.saturating_sub(FAST).as_secs_f64() / (SLOW - FAST).as_secs_f64();
+                gradient(t)
+            })
+        }))
+        .block(Block::bordered().title("Speed"))
+        .render(speed, buf);
+        self.summary().centered().render(summary, buf);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keys_combine_cases() {
+        let mut stats = KeyStats::default();
+        stats.record(None, "a", "a", None);
+        stats.record(None, "A", "s", None);
+        let stat = key_stat(&stats, 'a');
+        assert_eq!(stat.samples(), 2);
+        assert_eq!(stat.miss_rate(), Some(0.5));
+        assert_eq!(gradient(0f64), Color::Rgb(40, 220, 40));
+        assert_eq!(gradient(2f64), Color::Rgb(240, 40, 40));
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{GameMode, GameOptions},
+    daily::DAILY_LANGUAGE,
+    engine::GameStats,
+    profile::Profile,
+    quotes::{quote_language, QuoteLength},
+    text::CharBreakdown,
+    timer::Sample,
+};
+
+pub const HISTORY_VERSION: u32 = 1;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ResultMode {
+    Time {
+        time: u32,
+    },
+    Words {
+        words: u32,
+    },
+    Quote {
+        length: QuoteLength,
+    },
+    File {
+        file: String,
+    },
+    Code {
+        code: String,
+    },
+    Practice {
+        words: u32,
+    },
+    /// Daily challenges are kept apart from the history, see `daily`.
+    Daily,
+}
+
+impl ResultMode {
+    pub fn new(options: &GameOptions) -> Self {
+        match options.mode {
+            GameMode::Time => ResultMode::Time { time: options.time },
+            GameMode::Words => ResultMode::Words {
+                words: options.words,
+            },
+            GameMode::Quote => ResultMode::Quote {
+                length: options.quote_length,
+            },
+            GameMode::File => ResultMode::File {
+                file: options
+                    .file
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+            },
+            GameMode::Practice => ResultMode::Practice {
+                words: options.words,
+            },
+            GameMode::Code => ResultMode::Code {
+                code: options.code_language.clone(),
+            },
+            GameMode::Daily => ResultMode::Daily,
+        }
+    }
+    pub fn game_mode(&self) -> GameMode {
+        match self {
+            ResultMode::Time { .. } => GameMode::Time,
+            ResultMode::Words { .. } => GameMode::Words,
+            ResultMode::Quote { .. } => GameMode::Quote,
+            ResultMode::File { .. } => GameMode::File,
+            ResultMode::Code { .. } => GameMode::Code,
+            ResultMode::Practice { .. } => GameMode::Practice,
+            ResultMode::Daily => GameMode::Daily,
+        }
+    }
+    /// Name of the mode alone, as stored in the history.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResultMode::Time { .. } => "time",
+            ResultMode::Words { .. } => "words",
+            ResultMode::Quote { .. } => "quote",
+            ResultMode::File { .. } => "file",
+            ResultMode::Code { .. } => "code",
+            ResultMode::Practice { .. } => "practice",
+            ResultMode::Daily => "daily",
+        }
+    }
+    /// Setting of the mode, like the time limit or the quote length.
+    pub fn setting(&self) -> String {
+        match self {
+            ResultMode::Time { time } => time.to_string(),
+            ResultMode::Words { words } | ResultMode::Practice { words } => words.to_string(),
+            ResultMode::Quote { length } => length.name().to_string(),
+            ResultMode::File { file } => file.clone(),
+            ResultMode::Code { code } => code.clone(),
+            ResultMode::Daily => String::new(),
+        }
+    }
+    pub fn label(&self) -> String {
+        match self {
+            ResultMode::Time { time } => format!("time {} s", time),
+            ResultMode::Words { words } => format!("words {}", words),
+            ResultMode::Quote { length } => format!("quote {}", length.name()),
+            ResultMode::File { file } => format!("file {}", file),
+            ResultMode::Code { code } => format!("code {}", code),
+            ResultMode::Practice { words } => format!("practice {}", words),
+            ResultMode::Daily => "daily".to_string(),
+        }
+    }
+}
+
+/// Whether the text is generated from a word list, so text options apply.
+pub fn generated_text(options: &GameOptions) -> bool {
+    matches!(options.mode, GameMode::Time | GameMode::Words)
+}
+
+/// Language of the text a test with `options` is given.
+pub fn text_language(options: &GameOptions) -> &str {
+    match options.mode {
+        GameMode::Daily => DAILY_LANGUAGE,
+        GameMode::Quote => quote_language(&options.language),
+        _ => &options.language,
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameResult {
+    pub version: u32,
+    /// As in [`GameStats::timestamp`].
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub mode: ResultMode,
+    pub language: String,
+    /// Time actually spent typing, in seconds.
+    pub duration: f64,
+    pub wpm: f64,
+    pub acc: f64,
+    pub correct: usize,
+    pub typed: usize,
+    #[serde(default)]
+    pub raw_wpm: f64,
+    #[serde(default)]
+    pub consistency: f64,
+    #[serde(default)]
+    pub chars: CharBreakdown,
+    #[serde(default)]
+    pub samples: Vec<Sample>,
+    #[serde(default)]
+    pub punctuation: bool,
+    #[serde(default)]
+    pub numbers: bool,
+    /// Seed the words were generated with, to type the same text again.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl GameResult {
+    pub fn new(stats: &GameStats, options: &GameOptions) -> Self {
+        let generated = generated_text(options);
+        GameResult {
+            version: HISTORY_VERSION,
+            timestamp: stats.timestamp,
+            mode: ResultMode::new(options),
+            language: text_language(options).to_string(),
+            duration: stats.time.as_secs_f64(),
+            wpm: stats.wpm,
+            acc: stats.acc,
+            correct: stats.correct,
+            typed: stats.typed,
+            raw_wpm: stats.raw_wpm,
+            consistency: stats.consistency,
+            chars: stats.chars,
+            samples: stats.samples.clone(),
+            punctuation: generated && options.punctuation,
+            numbers: generated && options.numbers,
+            seed: stats.seed,
+        }
+    }
+    /// Whether a test with `options` would be comparable to this result.
+    pub fn matches_options(&self, options: &GameOptions) -> bool {
+        let generated = generated_text(options);
+        self.mode == ResultMode::new(options)
+            && self.language == text_language(options)
+            && self.punctuation == (generated && options.punctuation)
+            && self.numbers == (generated && options.numbers)
+    }
+    /// Whether two results were taken under the same conditions and their
+    /// scores can be compared.
+    pub fn comparable(&self, other: &GameResult) -> bool {
+        self.mode == other.mode
+            && self.language == other.language
+            && self.punctuation == other.punctuation
+            && self.numbers == other.numbers
+    }
+    pub fn label(&self) -> String {
+        let mut label = self.mode.label();
+        if self.punctuation {
+            label.push_str(" punct.");
+        }
+        if self.numbers {
+            label.push_str(" numbers");
+        }
+        label
+    }
+}
+
+#[cfg(test)]
+impl GameResult {
+    /// Result of a made-up test, to change the fields a test is about.
+    pub fn sample(mode: ResultMode) -> Self {
+        GameResult {
+            version: HISTORY_VERSION,
+            timestamp: 0,
+            mode,
+            language: "english".to_string(),
+            duration: 30.0,
+            wpm: 60.0,
+            acc: 95.5,
+            correct: 150,
+            typed: 157,
+            raw_wpm: 63.0,
+            consistency: 80.0,
+            chars: CharBreakdown::default(),
+            samples: vec![],
+            punctuation: false,
+            numbers: false,
+            seed: None,
+        }
+    }
+}
+
+pub fn history_path(profile: &Profile) -> io::Result<PathBuf> {
+    Ok(profile.data_dir()?.join("history.jsonl"))
+}
+
+/// History is stored as one JSON object per line. Lines that can't be parsed,
+/// e.g. left over from an interrupted write, or come from a newer version are
+/// skipped instead of failing the whole file.
+pub fn parse_history(buf: &str) -> Vec<GameResult> {
+    buf.lines()
+        .filter_map(|line| serde_json::from_str::<GameResult>(line).ok())
+        .filter(|result| result.version <= HISTORY_VERSION)
+        .collect()
+}
+
+pub fn load_history(profile: &Profile) -> io::Result<Vec<GameResult>> {
+    let mut buf = String::new();
+    match File::open(history_path(profile)?) {
+        Ok(mut file) => file.read_to_string(&mut buf)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    Ok(parse_history(&buf))
+}
+
+pub fn append_result(profile: &Profile, result: &GameResult) -> io::Result<()> {
+    append_line(history_path(profile)?, result)
+}
+
+/// Appends `value` as a line of JSON, starting a new line if the last write
+/// was cut short.
+pub fn append_line<T: Serialize>(path: PathBuf, value: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    if file.seek(SeekFrom::End(0))? > 0 {
+        file.seek(SeekFrom::End(-1))?;
+        let mut last = [0u8];
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.insert(0, '\n');
+        }
+    }
+    file.write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::{
+        engine::{Engine, Goal, InputEvent},
+        langs::WordSupplierBasic,
+        replay::Keystroke,
+        text::TextManagerDyn,
+    };
+
+    #[test]
+    fn corrupt_lines_are_skipped() {
+        let result = GameResult {
+            seed: Some(7),
+            ..GameResult::sample(ResultMode::Words { words: 25 })
+        };
+        let line = serde_json::to_string(&result).unwrap();
+        let newer = line.replace("\"version\":1", "\"version\":99");
+        let buf = format!("{line}\n{{\"version\":1,\"times\n{newer}\n{line}");
+        let history = parse_history(&buf);
+        assert_eq!(history.len(), 2);
+        assert!(history[1].mode == ResultMode::Words { words: 25 });
+        assert_eq!(history[1].seed, Some(7));
+    }
+
+    #[test]
+    fn json_result_names_mode_seed_and_language() {
+        let json = |mode, seed| {
+            let words = Box::new(WordSupplierBasic::new("ab"));
+            let mut engine =
+                Engine::new(TextManagerDyn::new(words), Goal::Words(1)).with_seed(seed);
+            for (i, c) in "ab".chars().enumerate() {
+                engine.handle(InputEvent {
+                    time: Duration::from_millis(i as u64 * 500),
+                    key: Keystroke::Char(c),
+                });
+            }
+            let options = GameOptions {
+                mode,
+                time: 30,
+                language: "german".to_string(),
+                ..Default::default()
+            };
+            let result = GameResult::new(&engine.result().unwrap(), &options);
+            serde_json::to_value(result).unwrap()
+        };
+        let daily = json(GameMode::Daily, Some(5));
+        assert_eq!(daily["mode"], "daily");
+        assert_eq!(daily["seed"], 5);
+        assert_eq!(daily["language"], DAILY_LANGUAGE);
+        let timed = json(GameMode::Time, Some(7));
+        assert_eq!(timed["mode"], "time");
+        assert_eq!(timed["time"], 30);
+        assert_eq!(timed["seed"], 7);
+        assert_eq!(timed["language"], "german");
+    }
+}
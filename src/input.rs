@@ -1,8 +1,15 @@
 use std::time::{Duration, SystemTime};
 
-use ratatui::crossterm::event::{poll, read, Event, KeyCode, KeyEventKind};
+use ratatui::crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::replay::Keystroke;
 
 pub fn read_key() -> std::io::Result<Option<KeyCode>> {
+    Ok(read_key_event()?.map(|key| key.code))
+}
+
+/// Like `read_key`, but keeps the modifiers.
+pub fn read_key_event() -> std::io::Result<Option<KeyEvent>> {
     let end_time = SystemTime::now()
         .checked_add(Duration::from_millis(100))
         .unwrap();
@@ -14,7 +21,7 @@ pub fn read_key() -> std::io::Result<Option<KeyCode>> {
         )? {
             if let Event::Key(key) = read()? {
                 if key.kind == KeyEventKind::Press {
-                    return Ok(Some(key.code));
+                    return Ok(Some(key));
                 }
             }
         } else {
@@ -23,6 +30,23 @@ pub fn read_key() -> std::io::Result<Option<KeyCode>> {
     }
 }
 
+/// Keystroke of the test typed with `key`, if any.
+pub fn keystroke(key: KeyEvent) -> Option<Keystroke> {
+    let word = key
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+    match key.code {
+        KeyCode::Char('w' | 'h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Keystroke::DeleteWord)
+        }
+        KeyCode::Backspace if word => Some(Keystroke::DeleteWord),
+        KeyCode::Backspace => Some(Keystroke::Backspace),
+        KeyCode::Enter => Some(Keystroke::Char('\n')),
+        KeyCode::Char(c) => Some(Keystroke::Char(c)),
+        _ => None,
+    }
+}
+
 pub fn read_key_block() -> std::io::Result<KeyCode> {
     loop {
         if let Event::Key(key) = read()? {
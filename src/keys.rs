@@ -0,0 +1,196 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::profile::Profile;
+
+/// Pauses longer than this are not counted as the time it took to find a key.
+const MAX_LATENCY: Duration = Duration::from_secs(2);
+/// Keys seen fewer times than this are not trusted to be weak.
+const MIN_SAMPLES: u32 = 5;
+/// Number of keys and bigrams targeted by practice mode.
+pub const PRACTICE_TARGETS: usize = 5;
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyStat {
+    pub hits: u32,
+    pub misses: u32,
+    /// Sum of latencies of timed correct hits, in milliseconds.
+    pub latency_ms: u64,
+    pub timed: u32,
+    /// What was typed on misses, with counts.
+    #[serde(default)]
+    pub typed_instead: BTreeMap<String, u32>,
+}
+
+impl KeyStat {
+    pub fn samples(&self) -> u32 {
+        self.hits + self.misses
+    }
+    pub fn accuracy(&self) -> f64 {
+        if self.samples() == 0 {
+            return 0f64;
+        }
+        self.hits as f64 * 100f64 / self.samples() as f64
+    }
+    pub fn mean_latency(&self) -> Option<Duration> {
+        (self.timed > 0).then(|| Duration::from_millis(self.latency_ms / self.timed as u64))
+    }
+    /// How much practice the key needs: slow keys score high and every miss
+    /// makes it worse. Counts are smoothed so a single miss doesn't dominate.
+    pub fn weakness(&self) -> f64 {
+        let miss_rate = (self.misses as f64 + 1f64) / (self.samples() as f64 + 2f64);
+        let latency = self.mean_latency().unwrap_or(MAX_LATENCY / 4).as_millis() as f64;
+        latency * (1f64 + 10f64 * miss_rate)
+    }
+    pub fn miss_rate(&self) -> Option<f64> {
+        (self.samples() > 0).then(|| self.misses as f64 / self.samples() as f64)
+    }
+    /// The most common wrong input for this key.
+    pub fn top_substitution(&self) -> Option<(&str, u32)> {
+        self.typed_instead
+            .iter()
+            .max_by_key(|(_, &count)| count)
+            .map(|(typed, &count)| (typed.as_str(), count))
+    }
+    pub fn merge(&mut self, other: &KeyStat) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.latency_ms += other.latency_ms;
+        self.timed += other.timed;
+        for (typed, count) in &other.typed_instead {
+            *self.typed_instead.entry(typed.clone()).or_default() += count;
+        }
+    }
+}
+
+/// Accuracy and latency of every expected key and pair of consecutive keys.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyStats {
+    pub keys: BTreeMap<String, KeyStat>,
+    pub bigrams: BTreeMap<String, KeyStat>,
+}
+
+impl KeyStats {
+    /// Records a keystroke of `typed` where `expected` follows `prev` in the
+    /// text. `latency` is the time since the previous keystroke.
+    pub fn record(
+        &mut self,
+        prev: Option<&str>,
+        expected: &str,
+        typed: &str,
+        latency: Option<Duration>,
+    ) {
+        if expected.trim().is_empty() {
+            return;
+        }
+        let correct = typed == expected;
+        let latency = latency.filter(|&latency| correct && latency <= MAX_LATENCY);
+        let update = |stat: &mut KeyStat| {
+            if correct {
+                stat.hits += 1;
+            } else {
+                stat.misses += 1;
+            }
+            if let Some(latency) = latency {
+                stat.latency_ms += latency.as_millis() as u64;
+                stat.timed += 1;
+            }
+        };
+        let key = self.keys.entry(expected.to_string()).or_default();
+        update(key);
+        if !correct {
+            *key.typed_instead.entry(typed.to_string()).or_default() += 1;
+        }
+        if let Some(prev) = prev.filter(|prev| !prev.trim().is_empty()) {
+            update(
+                self.bigrams
+                    .entry(format!("{}{}", prev, expected))
+                    .or_default(),
+            );
+        }
+    }
+    pub fn merge(&mut self, other: &KeyStats) {
+        for (key, stat) in &other.keys {
+            self.keys.entry(key.clone()).or_default().merge(stat);
+        }
+        for (bigram, stat) in &other.bigrams {
+            self.bigrams.entry(bigram.clone()).or_default().merge(stat);
+        }
+    }
+    /// The `count` keys and bigrams most in need of practice, weakest first.
+    pub fn weakest(&self, count: usize) -> Vec<(String, f64)> {
+        let mut weak = self
+            .keys
+            .iter()
+            .chain(&self.bigrams)
+            .filter(|(_, stat)| stat.samples() >= MIN_SAMPLES)
+            .map(|(key, stat)| (key.clone(), stat.weakness()))
+            .collect::<Vec<_>>();
+        weak.sort_by(|a, b| b.1.total_cmp(&a.1));
+        weak.truncate(count);
+        weak
+    }
+}
+
+pub fn key_stats_path(profile: &Profile) -> io::Result<PathBuf> {
+    Ok(profile.data_dir()?.join("keys.json"))
+}
+
+/// Key statistics summed over all finished tests. A file that can't be read
+/// as statistics starts them over, and is replaced by the next save.
+pub fn load_key_stats(profile: &Profile) -> io::Result<KeyStats> {
+    match fs::read_to_string(key_stats_path(profile)?) {
+        Ok(buf) => Ok(serde_json::from_str(&buf).unwrap_or_default()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(KeyStats::default()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_key_stats(profile: &Profile, stats: &KeyStats) -> io::Result<()> {
+    let path = key_stats_path(profile)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Written aside first, so an interrupted save leaves the old file whole.
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, serde_json::to_string(stats)?)?;
+    fs::rename(temp, path)
+}
+
+/// Adds the keys of a finished test to the stored statistics.
+pub fn add_key_stats(profile: &Profile, stats: &KeyStats) -> io::Result<()> {
+    let mut total = load_key_stats(profile)?;
+    total.merge(stats);
+    save_key_stats(profile, &total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn weakest_keys_are_slow_or_missed() {
+        let mut stats = KeyStats::default();
+        let fast = Some(Duration::from_millis(100));
+        let slow = Some(Duration::from_millis(400));
+        for _ in 0..10 {
+            stats.record(Some("a"), "s", "s", fast);
+            stats.record(Some("s"), "d", "d", slow);
+            stats.record(Some("d"), "f", "g", fast);
+            stats.record(Some(" "), "a", "a", Some(Duration::from_secs(5)));
+        }
+        assert_eq!(stats.keys["a"].timed, 0);
+        assert!(!stats.bigrams.contains_key(" a"));
+        let weakest = stats.weakest(4);
+        assert_eq!(weakest[0].0, "f");
+        assert_eq!(weakest[1].0, "df");
+        assert_eq!(weakest[2].0, "a");
+        assert_eq!(weakest[3].0, "d");
+        assert_eq!(stats.keys["f"].accuracy(), 0f64);
+        assert_eq!(stats.keys["f"].top_substitution(), Some(("g", 10)));
+        let mut total = stats.clone();
+        total.merge(&stats);
+        assert_eq!(total.keys["s"].hits, 20);
+    }
+}
@@ -1,60 +1,372 @@
-use std::{
-    fs::File,
-    io::{self, Read},
-    path::PathBuf,
+use std::{fs, io, path::Path};
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng, SeedableRng,
 };
+use rand_chacha::ChaCha8Rng;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
 
-use rand::{rngs::ThreadRng, thread_rng, Rng};
+use crate::{
+    assets::{list_assets, read_asset, read_bundled, LANGUAGES},
+    keys::{KeyStats, PRACTICE_TARGETS},
+};
 
 pub trait WordSupplier {
-    fn get_word(&mut self) -> Vec<char>;
+    fn get_word(&mut self) -> Option<String>;
+    /// Whether every word is a whole line, to be followed by a line break
+    /// instead of a space.
+    fn line_based(&self) -> bool {
+        false
+    }
+    /// Called with the keystrokes so far before every word, for suppliers
+    /// that adapt to the typist.
+    fn observe(&mut self, _stats: &KeyStats) {}
+}
+
+impl<Ws: WordSupplier + ?Sized> WordSupplier for Box<Ws> {
+    fn get_word(&mut self) -> Option<String> {
+        (**self).get_word()
+    }
+    fn line_based(&self) -> bool {
+        (**self).line_based()
+    }
+    fn observe(&mut self, stats: &KeyStats) {
+        (**self).observe(stats)
+    }
 }
 
+/// Number of graphemes in `words` joined by separators.
+pub fn text_length(words: &[String]) -> usize {
+    let length = words
+        .iter()
+        .map(|word| word.graphemes(true).count() + 1)
+        .sum::<usize>();
+    length.saturating_sub(1)
+}
+
+/// Random numbers for generating text. ChaCha gives the same stream for a
+/// seed on every platform and version, unlike `StdRng`.
+pub type TextRng = ChaCha8Rng;
+
 pub struct WordSupplierRandomized {
-    words: Vec<Vec<char>>,
-    rng: ThreadRng,
+    words: Vec<String>,
+    rng: TextRng,
+}
+
+pub fn available_languages() -> Vec<String> {
+    list_assets(LANGUAGES)
 }
 
 impl WordSupplierRandomized {
-    pub fn new(lang: &str) -> io::Result<Self> {
-        let mut file = {
-            let mut path = ["languages", lang].into_iter().collect::<PathBuf>();
-            path.set_extension("txt");
-            File::open(path)?
-        };
-        let mut buf = String::new();
-        file.read_to_string(&mut buf)?;
-        let words: Vec<Vec<char>> = buf
-            .split_ascii_whitespace()
-            .filter(|s| s.len() > 1)
-            .map(|s| s.to_lowercase().chars().collect::<Vec<_>>())
+    /// Words drawn from `lang`, always in the same order for the same seed.
+    pub fn new(lang: &str, seed: u64) -> io::Result<Self> {
+        let buf = read_asset(LANGUAGES, lang).map_err(|e| not_found(e, lang))?;
+        Self::from_list(lang, &buf, seed)
+    }
+    /// Words drawn from the word list shipped with the program, even if
+    /// another file of the same name is on disk.
+    pub fn bundled(lang: &str, seed: u64) -> io::Result<Self> {
+        let buf = read_bundled(LANGUAGES, lang).map_err(|e| not_found(e, lang))?;
+        Self::from_list(lang, buf, seed)
+    }
+    fn from_list(lang: &str, buf: &str, seed: u64) -> io::Result<Self> {
+        let words: Vec<String> = buf
+            .split_whitespace()
+            .filter(|s| s.graphemes(true).count() > 1)
+            .map(|s| s.to_lowercase().nfc().collect())
             .collect();
+        if words.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("word list `{}` is empty", lang),
+            ));
+        }
         Ok(WordSupplierRandomized {
             words,
-            rng: thread_rng(),
+            rng: TextRng::seed_from_u64(seed),
         })
     }
 }
 
+fn not_found(e: io::Error, lang: &str) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("word list `{}` not found", lang),
+        ),
+        _ => e,
+    }
+}
+
 impl WordSupplier for WordSupplierRandomized {
-    fn get_word(&mut self) -> Vec<char> {
-        let index = self.rng.gen::<usize>() % self.words.len();
-        self.words.get(index).unwrap().clone()
+    fn get_word(&mut self) -> Option<String> {
+        let index = self.rng.gen_range(0..self.words.len() as u64) as usize;
+        self.words.get(index).cloned()
+    }
+}
+
+/// Draws words from a word list, preferring words that contain the keys and
+/// bigrams most in need of practice. Targets are picked from past tests and
+/// picked again as keystrokes of the current test come in.
+pub struct WordSupplierAdaptive {
+    words: WordSupplierRandomized,
+    history: KeyStats,
+    targets: Vec<(String, f64)>,
+}
+
+impl WordSupplierAdaptive {
+    pub fn new(lang: &str, history: KeyStats, seed: u64) -> io::Result<Self> {
+        let targets = history.weakest(PRACTICE_TARGETS);
+        Ok(WordSupplierAdaptive {
+            words: WordSupplierRandomized::new(lang, seed)?,
+            history,
+            targets,
+        })
+    }
+}
+
+impl WordSupplier for WordSupplierAdaptive {
+    fn get_word(&mut self) -> Option<String> {
+        // Keep some plain words in so the text doesn't turn into drills.
+        if self.targets.is_empty() || self.words.rng.gen_bool(0.25) {
+            return self.words.get_word();
+        }
+        let weights = self.targets.iter().map(|(_, weakness)| weakness);
+        let target = match WeightedIndex::new(weights) {
+            Ok(index) => &self.targets[index.sample(&mut self.words.rng)].0,
+            Err(_) => return self.words.get_word(),
+        };
+        let words = &self.words.words;
+        let start = self.words.rng.gen_range(0..words.len() as u64) as usize;
+        words[start..]
+            .iter()
+            .chain(&words[..start])
+            .find(|word| word.contains(target.as_str()))
+            .cloned()
+            .or_else(|| self.words.get_word())
+    }
+    fn observe(&mut self, stats: &KeyStats) {
+        let mut total = self.history.clone();
+        total.merge(stats);
+        self.targets = total.weakest(PRACTICE_TARGETS);
     }
 }
 
+/// Words of a fixed text, in order and as written. Line breaks count as
+/// spaces and the supplier runs dry at the end of the text.
 pub struct WordSupplierBasic {
-    text: Vec<char>,
+    words: std::vec::IntoIter<String>,
+    length: usize,
 }
 
 impl WordSupplier for WordSupplierBasic {
-    fn get_word(&mut self) -> Vec<char> {
-        self.text.clone()
+    fn get_word(&mut self) -> Option<String> {
+        self.words.next()
     }
 }
 
 impl WordSupplierBasic {
-    pub fn new(text: Vec<char>) -> Self {
-        WordSupplierBasic { text }
+    pub fn new(text: &str) -> Self {
+        let words = text
+            .lines()
+            .flat_map(str::split_whitespace)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        WordSupplierBasic {
+            length: text_length(&words),
+            words: words.into_iter(),
+        }
+    }
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let buf = fs::read_to_string(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("could not read `{}`: {}", path.display(), e),
+            )
+        })?;
+        let words = WordSupplierBasic::new(&buf);
+        if words.length == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("file `{}` contains no text", path.display()),
+            ));
+        }
+        Ok(words)
+    }
+    /// Number of graphemes to type, counting single spaces between words.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
+
+/// Post-processes a stream of bare words into something closer to prose:
+/// capitalized sentences, punctuation and numbers.
+pub struct WordSupplierProse<Ws: WordSupplier> {
+    words: Ws,
+    punctuation: bool,
+    numbers: bool,
+    rng: TextRng,
+    sentence_start: bool,
+    closing: Option<char>,
+}
+
+impl<Ws: WordSupplier> WordSupplierProse<Ws> {
+    pub fn new(words: Ws, punctuation: bool, numbers: bool, seed: u64) -> Self {
+        let mut rng = TextRng::seed_from_u64(seed);
+        // Keep clear of the stream the words are drawn with.
+        rng.set_stream(1);
+        WordSupplierProse {
+            words,
+            punctuation,
+            numbers,
+            rng,
+            sentence_start: true,
+            closing: None,
+        }
+    }
+    fn punctuate(&mut self, word: String) -> String {
+        let mut word = if self.sentence_start {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        } else {
+            word
+        };
+        self.sentence_start = false;
+        match self.closing {
+            None if self.rng.gen_bool(0.05) => {
+                let (open, close) = if self.rng.gen_bool(0.5) {
+                    ('"', '"')
+                } else {
+                    ('(', ')')
+                };
+                word.insert(0, open);
+                self.closing = Some(close);
+            }
+            Some(close) if self.rng.gen_bool(0.3) => {
+                word.push(close);
+                self.closing = None;
+            }
+            _ => {}
+        }
+        let end = self.rng.gen::<f64>();
+        if end < 0.1 || end < 0.12 && self.closing.is_none() {
+            if let Some(close) = self.closing.take() {
+                word.push(close);
+            }
+            word.push(if end < 0.1 { '.' } else { '?' });
+            self.sentence_start = true;
+        } else if end < 0.2 && self.closing.is_none() {
+            word.push(',');
+        }
+        word
+    }
+}
+
+impl<Ws: WordSupplier> WordSupplier for WordSupplierProse<Ws> {
+    fn get_word(&mut self) -> Option<String> {
+        let word = if self.numbers && self.rng.gen_bool(0.1) {
+            self.rng.gen_range(0..10000).to_string()
+        } else {
+            self.words.get_word()?
+        };
+        if self.punctuation {
+            Some(self.punctuate(word))
+        } else {
+            Some(word)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bundled_languages_are_listed() {
+        let languages = available_languages();
+        for lang in [
+            "english",
+            "english_1k",
+            "english_10k",
+            "russian",
+            "japanese",
+        ] {
+            assert!(languages.iter().any(|l| l == lang));
+            assert!(WordSupplierRandomized::new(lang, 0).is_ok());
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_words() {
+        let words = |seed| {
+            let words = WordSupplierRandomized::new("english", seed).unwrap();
+            let mut prose = WordSupplierProse::new(words, true, true, seed);
+            (0..50)
+                .map(|_| prose.get_word().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words(7), words(7));
+        assert_ne!(words(7), words(8));
+        // Drawn the same way on 32 and 64-bit platforms, for daily tests.
+        let mut plain = WordSupplierRandomized::bundled("english", 7).unwrap();
+        let first = (0..4)
+            .map(|_| plain.get_word().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(first, ["there", "who", "while", "here"]);
+    }
+
+    #[test]
+    fn missing_language_is_an_error() {
+        let error = WordSupplierRandomized::new("klingon", 0).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("klingon"));
+    }
+
+    #[test]
+    fn file_text_keeps_case_and_punctuation() {
+        let mut words = WordSupplierBasic::new("Hello, world!\n\n  See `README.md`.\n");
+        assert_eq!(words.length(), 30);
+        assert_eq!(words.get_word().unwrap(), "Hello,");
+        assert_eq!(words.get_word().unwrap(), "world!");
+        assert_eq!(words.get_word().unwrap(), "See");
+        assert_eq!(words.get_word().unwrap(), "`README.md`.");
+        assert_eq!(words.get_word(), None);
+        let error = WordSupplierBasic::from_file(Path::new("/nonexistent")).err();
+        assert_eq!(error.unwrap().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn prose_starts_sentences_with_capitals() {
+        let mut words =
+            WordSupplierProse::new(WordSupplierBasic::new(&"word ".repeat(1000)), true, true, 0);
+        let mut sentence_start = true;
+        for _ in 0..1000 {
+            let word = words.get_word().unwrap();
+            let letters = word.trim_start_matches(['"', '(']);
+            if sentence_start {
+                assert!(letters.starts_with(|c: char| c.is_uppercase() || c.is_ascii_digit()));
+            }
+            sentence_start = word.ends_with(['.', '?']);
+        }
+        let mut words = WordSupplierProse::new(WordSupplierBasic::new("word"), false, false, 0);
+        assert_eq!(words.get_word().unwrap(), "word");
+    }
+
+    #[test]
+    fn adaptive_words_contain_weak_keys() {
+        let mut history = KeyStats::default();
+        for _ in 0..10 {
+            history.record(None, "q", "w", None);
+        }
+        let mut words = WordSupplierAdaptive::new("english_10k", history, 0).unwrap();
+        let with_q = (0..100)
+            .filter(|_| words.get_word().unwrap().contains('q'))
+            .count();
+        assert!(with_q > 50);
     }
 }
@@ -1,4 +1,18 @@
+use std::{io, path::PathBuf};
+
 use ratatui::{prelude::*, widgets::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::GameStats,
+    heatmap::{KeyboardHeatmap, HEATMAP_HEIGHT},
+    langs::WordSupplier,
+    profile::Profile,
+    race::{Race, RaceRanking},
+    replay::{save_replay, KeyLog},
+    text::{display_width, Grapheme, TextManager, WidgetData},
+    timer::Sample,
+};
 
 pub struct AppLayout {
     pub gauge_area: Rect,
@@ -28,6 +42,58 @@ impl AppLayout {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Default,
+    Mono,
+    Ocean,
+}
+
+impl Theme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::Mono => "mono",
+            Theme::Ocean => "ocean",
+        }
+    }
+    fn correct(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().green(),
+            Theme::Mono => Style::new().white().bold(),
+            Theme::Ocean => Style::new().cyan(),
+        }
+    }
+    fn wrong(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().blue().on_red(),
+            Theme::Mono => Style::new().white().reversed(),
+            Theme::Ocean => Style::new().white().on_magenta(),
+        }
+    }
+    fn untyped(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().blue(),
+            Theme::Mono | Theme::Ocean => Style::new().dark_gray(),
+        }
+    }
+    fn ghost(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().on_dark_gray(),
+            Theme::Mono => Style::new().underlined(),
+            Theme::Ocean => Style::new().on_blue(),
+        }
+    }
+    fn gauge(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().fg(Color::Blue).bg(Color::Red),
+            Theme::Mono => Style::new().fg(Color::White).bg(Color::DarkGray),
+            Theme::Ocean => Style::new().fg(Color::Cyan).bg(Color::Blue),
+        }
+    }
+}
+
 pub struct TestLines<'a> {
     prev_line: Line<'a>,
     line: Line<'a>,
@@ -35,41 +101,60 @@ pub struct TestLines<'a> {
 }
 
 impl<'a> TestLines<'a> {
-    fn char_to_line(test_line: &[char], user_line: &[char]) -> Line<'a> {
+    fn char_to_line(test_line: &[Grapheme], user_line: &[Grapheme], theme: Theme) -> Line<'a> {
         test_line
             .iter()
             .zip(user_line.iter().map(Some).chain(std::iter::repeat(None)))
             .map(|(c, u)| {
-                let span = Span::raw(c.to_string());
+                let span = match c.as_str() {
+                    "\n" => Span::raw("⏎"),
+                    _ => Span::raw(c.clone()),
+                };
                 match u {
                     Some(u) => {
                         if c == u {
-                            span.green()
+                            span.style(theme.correct())
                         } else {
-                            span.blue().on_red()
+                            span.style(theme.wrong())
                         }
                     }
-                    None => span.blue(),
+                    None => span.style(theme.untyped()),
                 }
             })
             .collect()
     }
 
     pub fn new(
-        prev_line: &[char],
-        line: &[char],
-        next_line: &[char],
-        prev_user_text: &[char],
-        user_text: &[char],
+        prev_line: &[Grapheme],
+        line: &[Grapheme],
+        next_line: &[Grapheme],
+        prev_user_text: &[Grapheme],
+        user_text: &[Grapheme],
+        theme: Theme,
     ) -> Self {
         TestLines {
-            prev_line: TestLines::char_to_line(prev_line, prev_user_text),
-            line: TestLines::char_to_line(line, user_text),
-            next_line: TestLines::char_to_line(next_line, &[]),
+            prev_line: TestLines::char_to_line(prev_line, prev_user_text, theme),
+            line: TestLines::char_to_line(line, user_text, theme),
+            next_line: TestLines::char_to_line(next_line, &[], theme),
         }
     }
 }
 
+impl<'a> TestLines<'a> {
+    /// Marks the ghost cursor, `position` graphemes from the start of the
+    /// first line.
+    pub fn with_ghost(mut self, mut position: usize, theme: Theme) -> Self {
+        for line in [&mut self.prev_line, &mut self.line, &mut self.next_line] {
+            if let Some(span) = line.spans.get_mut(position) {
+                span.style = span.style.patch(theme.ghost());
+                break;
+            }
+            position -= line.spans.len();
+        }
+        self
+    }
+}
+
 impl<'a> Widget for TestLines<'a> {
     fn render(self, area: Rect, buf: &mut Buffer)
     where
@@ -92,14 +177,113 @@ impl<'a> Widget for TestLines<'a> {
     }
 }
 
+pub struct TextView<'a, Ws: WordSupplier> {
+    text_manager: &'a mut TextManager<Ws>,
+    theme: Theme,
+    ghost: Option<usize>,
+}
+
+impl<Ws: WordSupplier> TextManager<Ws> {
+    pub fn view(&mut self, theme: Theme) -> TextView<'_, Ws> {
+        TextView {
+            text_manager: self,
+            theme,
+            ghost: None,
+        }
+    }
+}
+
+impl<Ws: WordSupplier> TextView<'_, Ws> {
+    /// Also shows a ghost cursor at `position` in the text.
+    pub fn ghost(self, position: Option<usize>) -> Self {
+        TextView {
+            ghost: position,
+            ..self
+        }
+    }
+}
+
+impl<Ws: WordSupplier> StatefulWidget for TextView<'_, Ws> {
+    type State = Option<(u16, u16)>;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let WidgetData {
+            start,
+            prev_line,
+            line,
+            next_line,
+            prev_user_text,
+            user_text,
+        } = self.text_manager.widget_data(area.width as usize);
+        let mut text = TestLines::new(
+            prev_line,
+            line,
+            next_line,
+            prev_user_text,
+            user_text,
+            self.theme,
+        );
+        if let Some(ghost) = self.ghost.and_then(|ghost| ghost.checked_sub(start)) {
+            text = text.with_ghost(ghost, self.theme);
+        }
+        text.render(area, buf);
+        let cursor = if user_text.is_empty() && prev_user_text.len() < prev_line.len() {
+            let column = display_width(&prev_line[..prev_user_text.len()]);
+            (area.left() + column as u16, area.top())
+        } else {
+            let column = display_width(&line[..user_text.len().min(line.len())]);
+            (area.left() + column as u16, area.top() + 1)
+        };
+        *state = Some(cursor);
+    }
+}
+
 pub struct GameStatsScreen {
-    wpm: f64,
-    acc: f64,
+    stats: GameStats,
+    personal_best: bool,
+    save_error: Option<io::Error>,
+    keys_error: Option<io::Error>,
+    replay_saved: Option<io::Result<PathBuf>>,
+    /// Race the test was part of, still updated while others finish.
+    race: Option<Box<Race>>,
+    /// For a daily challenge, whether this attempt was scored.
+    daily: Option<bool>,
 }
 
 impl GameStatsScreen {
-    pub fn new(wpm: f64, acc: f64) -> Self {
-        GameStatsScreen { wpm, acc }
+    pub fn new(stats: GameStats, personal_best: bool, save_error: Option<io::Error>) -> Self {
+        GameStatsScreen {
+            stats,
+            personal_best,
+            save_error,
+            keys_error: None,
+            replay_saved: None,
+            race: None,
+            daily: None,
+        }
+    }
+    pub fn with_keys_error(self, keys_error: Option<io::Error>) -> Self {
+        GameStatsScreen { keys_error, ..self }
+    }
+    pub fn with_daily(self, scored: bool) -> Self {
+        GameStatsScreen {
+            daily: Some(scored),
+            ..self
+        }
+    }
+    pub fn with_race(self, race: Box<Race>) -> Self {
+        GameStatsScreen {
+            race: Some(race),
+            ..self
+        }
+    }
+    pub fn race_mut(&mut self) -> Option<&mut Race> {
+        self.race.as_deref_mut()
+    }
+    pub fn key_log(&self) -> &KeyLog {
+        &self.stats.log
+    }
+    pub fn save_replay(&mut self, profile: &Profile) {
+        self.replay_saved = Some(save_replay(profile, &self.stats.log));
     }
 }
 
@@ -114,42 +298,188 @@ impl Widget for &GameStatsScreen {
             stat_area,
             text_area,
         } = AppLayout::new(area);
-        Line::raw("Time ended!")
-            .bold()
-            .centered()
-            .render(gauge_area, buf);
-        Line::raw("Press Tab to restart or Esc to quit")
+        let title = match self.daily {
+            Some(true) => {
+                Line::raw("Daily challenge done - see how it ranks on the leaderboard").yellow()
+            }
+            Some(false) => Line::raw("Daily challenge already done today - this one is not scored"),
+            None if self.personal_best => Line::raw("Test finished - new personal best!").yellow(),
+            None => Line::raw("Test finished!"),
+        };
+        title.bold().centered().render(gauge_area, buf);
+        Line::raw("Press Tab to restart, Esc to quit, r to replay or s to save the replay")
             .bold()
             .centered()
             .render(stat_area, buf);
-        let [top_line, bot_line] = Layout::vertical([Length(1), Length(1)]).areas(text_area);
-        Line::raw(format!("WPM: {:.02}", self.wpm))
-            .bold()
-            .centered()
-            .render(top_line, buf);
-        Line::raw(format!("Accuracy: {:.02}", self.acc))
-            .bold()
-            .centered()
-            .render(bot_line, buf);
+        let [top_line, mid_line, bot_line] =
+            Layout::vertical([Length(1), Length(1), Length(1)]).areas(text_area);
+        let chars = self.stats.chars;
+        Line::raw(format!(
+            "WPM: {:.02}  Raw: {:.02}",
+            self.stats.wpm, self.stats.raw_wpm
+        ))
+        .bold()
+        .centered()
+        .render(top_line, buf);
+        Line::raw(format!(
+            "Accuracy: {:.02}  Consistency: {:.02}",
+            self.stats.acc, self.stats.consistency
+        ))
+        .bold()
+        .centered()
+        .render(mid_line, buf);
+        Line::raw(format!(
+            "Time: {:.02} s  Characters: {}/{}/{}/{}  Corrected: {}",
+            self.stats.time.as_secs_f64(),
+            chars.correct,
+            chars.incorrect,
+            chars.extra,
+            chars.missed,
+            chars.corrected
+        ))
+        .bold()
+        .centered()
+        .render(bot_line, buf);
+        let below = Rect {
+            y: text_area.bottom(),
+            height: area.bottom().saturating_sub(text_area.bottom()),
+            ..text_area
+        };
+        // The ranking of a race takes the place of the heatmap.
+        let heatmap_height = match &self.race {
+            Some(race) => race.racers.len() as u16 + 3,
+            None => HEATMAP_HEIGHT,
+        };
+        let [_, source_line, error_line, heatmap_area, chart_area] = Layout::vertical([
+            Length(1),
+            Length(1),
+            Length(1),
+            Length(heatmap_height),
+            Fill(1),
+        ])
+        .areas(below);
+        if let Some(source) = &self.stats.source {
+            Line::raw(format!("-- {}", source))
+                .italic()
+                .centered()
+                .render(source_line, buf);
+        } else if let Some(seed) = self.stats.seed {
+            Line::raw(format!("Seed: {}", seed))
+                .italic()
+                .centered()
+                .render(source_line, buf);
+        }
+        let message = match (&self.save_error, &self.keys_error, &self.replay_saved) {
+            (Some(error), _, _) => Some(Line::raw(format!("Result not saved: {}", error)).red()),
+            (_, Some(error), _) => {
+                Some(Line::raw(format!("Key statistics not saved: {}", error)).red())
+            }
+            (_, _, Some(Err(error))) => {
+                Some(Line::raw(format!("Replay not saved: {}", error)).red())
+            }
+            (_, _, Some(Ok(path))) => {
+                Some(Line::raw(format!("Replay saved to {}", path.display())).green())
+            }
+            (None, None, None) => None,
+        };
+        if let Some(message) = message {
+            message.centered().render(error_line, buf);
+        }
+        match &self.race {
+            Some(race) => RaceRanking(race).render(heatmap_area, buf),
+            None => KeyboardHeatmap::new(&self.stats.keys).render(heatmap_area, buf),
+        }
+        if !self.stats.samples.is_empty() {
+            let raw = samples_points(&self.stats.samples, |s| s.raw_wpm);
+            let wpm = samples_points(&self.stats.samples, |s| s.wpm);
+            let errors = self
+                .stats
+                .samples
+                .iter()
+                .filter(|s| s.errors > 0)
+                .map(|s| (s.time, s.raw_wpm))
+                .collect::<Vec<_>>();
+            samples_chart(&raw, &wpm, &errors, self.stats.time.as_secs_f64())
+                .render(chart_area, buf);
+        }
     }
 }
 
+fn samples_points(samples: &[Sample], value: impl Fn(&Sample) -> f64) -> Vec<(f64, f64)> {
+    samples.iter().map(|s| (s.time, value(s))).collect()
+}
+
+fn samples_chart<'a>(
+    raw: &'a [(f64, f64)],
+    wpm: &'a [(f64, f64)],
+    errors: &'a [(f64, f64)],
+    time: f64,
+) -> Chart<'a> {
+    let max_wpm = raw
+        .iter()
+        .chain(wpm)
+        .map(|p| p.1)
+        .fold(1f64, f64::max)
+        .ceil();
+    let datasets = vec![
+        Dataset::default()
+            .name("raw")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .dark_gray()
+            .data(raw),
+        Dataset::default()
+            .name("wpm")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .yellow()
+            .data(wpm),
+        Dataset::default()
+            .name("errors")
+            .marker(symbols::Marker::Dot)
+            .graph_type(GraphType::Scatter)
+            .red()
+            .data(errors),
+    ];
+    Chart::new(datasets)
+        .x_axis(
+            Axis::default()
+                .bounds([0f64, time.max(1f64)])
+                .labels(vec!["0".into(), format!("{:.0} s", time).into()]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([0f64, max_wpm])
+                .labels(vec!["0".into(), format!("{}", max_wpm).into()]),
+        )
+}
+
 pub fn get_ui_live_widgets<'a>(
     wpm: usize,
     acc: usize,
     gauge_percent: u16,
+    ghost_gap: Option<i64>,
+    theme: Theme,
 ) -> (Gauge<'a>, Line<'a>) {
     let gauge = Gauge::default()
-        .gauge_style(Style::default().fg(Color::Blue).bg(Color::Red))
+        .gauge_style(theme.gauge())
         .percent(gauge_percent)
         .label(Span::default())
         .use_unicode(true);
-    let stat_line = Line::from(vec![
+    let mut stat_line = Line::from(vec![
         "WPM: ".bold(),
         wpm.to_string().into(),
         " Acc: ".bold(),
         acc.to_string().into(),
     ])
     .left_aligned();
+    if let Some(gap) = ghost_gap {
+        stat_line.push_span(" Ghost: ".bold());
+        stat_line.push_span(match gap {
+            0 => "even".into(),
+            gap if gap > 0 => format!("{} ahead", gap).green(),
+            gap => format!("{} behind", -gap).red(),
+        });
+    }
     (gauge, stat_line)
 }
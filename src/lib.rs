@@ -0,0 +1,43 @@
+//! Typing speed test. The engine and what it draws on work without a
+//! terminal, the interface on top of it comes with the default `tui` feature.
+
+pub mod assets;
+pub mod code;
+pub mod engine;
+pub mod ghost;
+pub mod keys;
+pub mod langs;
+pub mod profile;
+pub mod quotes;
+pub mod replay;
+pub mod text;
+pub mod timer;
+
+#[cfg(feature = "tui")]
+pub mod app;
+#[cfg(feature = "tui")]
+pub mod cli;
+#[cfg(feature = "tui")]
+pub mod config;
+#[cfg(feature = "tui")]
+pub mod daily;
+#[cfg(feature = "tui")]
+pub mod export;
+#[cfg(feature = "tui")]
+pub mod game;
+#[cfg(feature = "tui")]
+pub mod heatmap;
+#[cfg(feature = "tui")]
+pub mod history;
+#[cfg(feature = "tui")]
+pub mod input;
+#[cfg(feature = "tui")]
+pub mod layout;
+#[cfg(feature = "tui")]
+pub mod playback;
+#[cfg(feature = "tui")]
+pub mod race;
+#[cfg(feature = "tui")]
+pub mod stats;
+#[cfg(feature = "tui")]
+pub mod welcome;
@@ -1,13 +1,9 @@
-pub mod app;
-pub mod game;
-pub mod input;
-pub mod langs;
-pub mod layout;
-pub mod text;
-pub mod timer;
-pub mod welcome;
-
-use app::start_game;
+use clap::Parser;
+use speedtype::{
+    app::start_game,
+    cli::{Cli, Command},
+    export::export_results,
+};
 
 fn initialize_panic_handler() {
     std::panic::set_hook(Box::new(|panic_info| {
@@ -24,7 +20,17 @@ fn initialize_panic_handler() {
     }));
 }
 
-fn main() -> std::io::Result<()> {
-    initialize_panic_handler();
-    start_game()
+fn main() {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Some(Command::Export(args)) => cli.profile().and_then(|p| export_results(&p, args)),
+        None => {
+            initialize_panic_handler();
+            start_game(cli)
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("speedtype: {}", e);
+        std::process::exit(1);
+    }
 }
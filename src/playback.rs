@@ -0,0 +1,171 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use ratatui::crossterm::event::KeyCode;
+
+use crate::{
+    input::read_key,
+    layout::Theme,
+    replay::{KeyLog, WordSupplierList},
+    text::TextManager,
+    timer::wpm_from_letters,
+};
+
+const SPEEDS: [u32; 3] = [1, 2, 4];
+
+pub enum ReplayAction {
+    Continue,
+    Exit,
+}
+
+/// Plays a key log back through a fresh `TextManager`, so it renders exactly
+/// like the live test did.
+pub struct ReplayScreen {
+    log: KeyLog,
+    text_manager: TextManager<WordSupplierList>,
+    next: usize,
+    time: Duration,
+    last_tick: Instant,
+    speed: u32,
+    theme: Theme,
+    /// Opened from the statistics screen rather than after a test.
+    pub from_history: bool,
+}
+
+impl ReplayScreen {
+    pub fn new(log: KeyLog, theme: Theme, from_history: bool) -> Self {
+        ReplayScreen {
+            text_manager: log.text_manager(),
+            log,
+            next: 0,
+            time: Duration::ZERO,
+            last_tick: Instant::now(),
+            speed: SPEEDS[0],
+            theme,
+            from_history,
+        }
+    }
+    fn restart(&mut self) {
+        self.text_manager = self.log.text_manager();
+        self.next = 0;
+        self.time = Duration::ZERO;
+    }
+    fn finished(&self) -> bool {
+        self.next >= self.log.keys.len()
+    }
+    /// Advances the replay clock and applies every keystroke that is due.
+    fn tick(&mut self) {
+        let now = Instant::now();
+        if !self.finished() {
+            self.time += (now - self.last_tick) * self.speed;
+        }
+        self.last_tick = now;
+        while let Some(key) = self.log.keys.get(self.next) {
+            if Duration::from_millis(key.ms) > self.time {
+                break;
+            }
+            key.key.apply(&mut self.text_manager);
+            self.next += 1;
+        }
+    }
+    pub fn handle_events(&mut self) -> io::Result<ReplayAction> {
+        if let Some(key) = read_key()? {
+            match key {
+                KeyCode::Esc => return Ok(ReplayAction::Exit),
+                KeyCode::Char('r') => self.restart(),
+                KeyCode::Char(c) => {
+                    if let Some(&speed) = SPEEDS.iter().find(|s| c.to_digit(10) == Some(**s)) {
+                        self.speed = speed;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.tick();
+        Ok(ReplayAction::Continue)
+    }
+}
+
+mod widget {
+    use ratatui::prelude::*;
+
+    use crate::layout::AppLayout;
+
+    use super::{wpm_from_letters, ReplayScreen};
+
+    impl StatefulWidget for &mut ReplayScreen {
+        type State = Option<(u16, u16)>;
+        fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+            let AppLayout {
+                gauge_area,
+                stat_area,
+                text_area,
+            } = AppLayout::new(area);
+            let status = if self.finished() {
+                "finished"
+            } else {
+                "playing"
+            };
+            Line::raw(format!(
+                "Replay {}x ({}) - 1/2/4 speed, r restart, Esc back",
+                self.speed, status
+            ))
+            .bold()
+            .centered()
+            .render(gauge_area, buf);
+            let wpm = if self.time.is_zero() {
+                0f64
+            } else {
+                wpm_from_letters(self.text_manager.correct(), self.time)
+            };
+            Line::raw(format!(
+                "WPM: {:.0}  Time: {:.1} s",
+                wpm,
+                self.time.as_secs_f64()
+            ))
+            .centered()
+            .render(stat_area, buf);
+            self.text_manager
+                .view(self.theme)
+                .render(text_area, buf, state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        langs::WordSupplierBasic,
+        replay::{KeyRecorder, Keystroke},
+    };
+
+    #[test]
+    fn replay_reproduces_the_test() {
+        let mut text_manager = TextManager::new(WordSupplierBasic::new("ab cd ef"));
+        let mut recorder = KeyRecorder::new();
+        let keys = "ab cx"
+            .chars()
+            .map(Keystroke::Char)
+            .chain([Keystroke::Backspace, Keystroke::DeleteWord])
+            .chain("cd".chars().map(Keystroke::Char));
+        for (i, key) in keys.enumerate() {
+            key.apply(&mut text_manager);
+            recorder.record(Duration::from_millis(i as u64 * 100), key);
+        }
+        let log = recorder.finish(&text_manager, 42);
+        let buf = serde_json::to_string(&log).unwrap();
+        let log: KeyLog = serde_json::from_str(&buf).unwrap();
+        assert_eq!(log.keys.len(), 9);
+
+        let mut replay = ReplayScreen::new(log, Theme::Default, false);
+        replay.speed = 4;
+        replay.last_tick -= Duration::from_secs(1);
+        replay.tick();
+        assert!(replay.finished());
+        assert_eq!(replay.text_manager.breakdown(), text_manager.breakdown());
+        assert_eq!(replay.text_manager.position(), 5);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{fs, io, path::PathBuf};
+
+pub const DEFAULT_PROFILE: &str = "default";
+pub const MAX_NAME: usize = 24;
+
+/// Someone using the program, with their own options, history and key
+/// statistics. The default profile keeps its files where they were before
+/// there were profiles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    name: String,
+}
+
+fn speedtype_dir(base: Option<PathBuf>, kind: &str) -> io::Result<PathBuf> {
+    let mut path = base.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no user {} directory", kind),
+        )
+    })?;
+    path.push("speedtype");
+    Ok(path)
+}
+
+impl Profile {
+    pub fn new(name: &str) -> io::Result<Self> {
+        let name = name.trim();
+        let valid = !name.is_empty()
+            && name.chars().count() <= MAX_NAME
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid profile name `{}`, use up to {} letters, digits, `-` and `_`",
+                    name, MAX_NAME
+                ),
+            ));
+        }
+        Ok(Profile {
+            name: name.to_string(),
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    fn scoped(&self, mut path: PathBuf) -> PathBuf {
+        if self.name != DEFAULT_PROFILE {
+            path.push("profiles");
+            path.push(&self.name);
+        }
+        path
+    }
+    /// Directory of the history, key statistics and replays.
+    pub fn data_dir(&self) -> io::Result<PathBuf> {
+        Ok(self.scoped(speedtype_dir(dirs::data_dir(), "data")?))
+    }
+    pub fn config_dir(&self) -> io::Result<PathBuf> {
+        Ok(self.scoped(speedtype_dir(dirs::config_dir(), "config")?))
+    }
+    /// Makes the profile show up in `list_profiles` before it has any results.
+    pub fn create(&self) -> io::Result<()> {
+        fs::create_dir_all(self.data_dir()?)
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            name: DEFAULT_PROFILE.to_string(),
+        }
+    }
+}
+
+/// The default profile, then every other one by name.
+pub fn list_profiles() -> Vec<Profile> {
+    let mut profiles = Profile::default()
+        .data_dir()
+        .and_then(|dir| fs::read_dir(dir.join("profiles")))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            entry.file_type().ok()?.is_dir().then_some(())?;
+            Profile::new(entry.file_name().to_str()?).ok()
+        })
+        .filter(|profile| profile.name != DEFAULT_PROFILE)
+        .collect::<Vec<_>>();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles.insert(0, Profile::default());
+    profiles
+}
+
+fn last_profile_path() -> io::Result<PathBuf> {
+    Ok(speedtype_dir(dirs::data_dir(), "data")?.join("profile"))
+}
+
+/// Profile used last time, so people don't have to pick theirs every run.
+pub fn last_profile() -> Profile {
+    fs::read_to_string(last_profile_path().unwrap_or_default())
+        .ok()
+        .and_then(|name| Profile::new(&name).ok())
+        .unwrap_or_default()
+}
+
+pub fn set_last_profile(profile: &Profile) -> io::Result<()> {
+    let path = last_profile_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, &profile.name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn profiles_get_their_own_directory() {
+        assert!(Profile::new("ann-2").is_ok());
+        assert!(Profile::new("").is_err());
+        assert!(Profile::new("../ann").is_err());
+        let default = Profile::default().data_dir().unwrap();
+        let ann = Profile::new(" ann ").unwrap().data_dir().unwrap();
+        assert_eq!(ann, default.join("profiles").join("ann"));
+        assert_eq!(list_profiles()[0], Profile::default());
+    }
+}
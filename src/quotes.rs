@@ -0,0 +1,128 @@
+use std::io;
+
+use rand::{seq::SliceRandom, thread_rng};
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::assets::{list_assets, read_asset, QUOTES};
+
+/// Quotes used for languages without quotes of their own.
+pub const DEFAULT_QUOTES: &str = "english";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteLength {
+    Short,
+    Medium,
+    Long,
+    Any,
+}
+
+impl QuoteLength {
+    fn matches(&self, len: usize) -> bool {
+        match self {
+            QuoteLength::Short => len < 100,
+            QuoteLength::Medium => (100..300).contains(&len),
+            QuoteLength::Long => len >= 300,
+            QuoteLength::Any => true,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuoteLength::Short => "short",
+            QuoteLength::Medium => "medium",
+            QuoteLength::Long => "long",
+            QuoteLength::Any => "any",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Quote {
+    pub text: String,
+    pub source: String,
+}
+
+impl Quote {
+    pub fn length(&self) -> usize {
+        self.text.graphemes(true).count()
+    }
+}
+
+/// Quote files hold blocks separated by blank lines. Every block is the quote
+/// text, possibly wrapped over several lines, followed by a `-- source` line.
+pub fn parse_quotes(buf: &str) -> Vec<Quote> {
+    buf.split("\n\n")
+        .filter_map(|block| {
+            let mut text = vec![];
+            let mut source = String::new();
+            for line in block.lines() {
+                match line.trim().strip_prefix("--") {
+                    Some(s) => source = s.trim().to_string(),
+                    None => text.extend(line.split_whitespace()),
+                }
+            }
+            if text.is_empty() {
+                return None;
+            }
+            Some(Quote {
+                text: text.join(" "),
+                source,
+            })
+        })
+        .collect()
+}
+
+pub fn load_quotes(lang: &str) -> io::Result<Vec<Quote>> {
+    let buf = read_asset(QUOTES, lang)?;
+    Ok(parse_quotes(&buf.replace("\r\n", "\n")))
+}
+
+/// Quotes of `lang`, if there are any, otherwise the default ones.
+pub fn quote_language(lang: &str) -> &str {
+    if list_assets(QUOTES).iter().any(|name| name == lang) {
+        lang
+    } else {
+        DEFAULT_QUOTES
+    }
+}
+
+pub fn random_quote(lang: &str, length: QuoteLength) -> io::Result<Quote> {
+    let quotes = load_quotes(lang)?;
+    let mut rng = thread_rng();
+    let fitting = quotes
+        .iter()
+        .filter(|q| length.matches(q.length()))
+        .collect::<Vec<_>>();
+    fitting
+        .choose(&mut rng)
+        .copied()
+        .or_else(|| quotes.choose(&mut rng))
+        .cloned()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no quotes found"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_multiline_quote() {
+        let quotes = parse_quotes("first  line\nsecond\n-- Someone, Somewhere\n\nno source\n\n\n");
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(quotes[0].text, "first line second");
+        assert_eq!(quotes[0].source, "Someone, Somewhere");
+        assert_eq!(quotes[1].text, "no source");
+        assert!(quotes[1].source.is_empty());
+    }
+
+    #[test]
+    fn bundled_quotes_cover_all_lengths() {
+        let quotes = load_quotes("english").unwrap();
+        for length in [QuoteLength::Short, QuoteLength::Medium, QuoteLength::Long] {
+            assert!(quotes.iter().any(|q| length.matches(q.length())));
+        }
+        assert_eq!(quote_language("klingon"), DEFAULT_QUOTES);
+    }
+}
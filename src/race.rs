@@ -0,0 +1,613 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use ratatui::crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{input::read_key, langs::text_length, replay::WordSupplierList};
+
+pub const DEFAULT_PORT: u16 = 7878;
+/// Time between the host starting the race and the first keystroke.
+const COUNTDOWN: Duration = Duration::from_secs(3);
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_NAME: usize = 16;
+
+/// Text every racer types, chosen by the host.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RaceText {
+    pub words: Vec<String>,
+    pub line_based: bool,
+    pub skip_indent: bool,
+    pub source: Option<String>,
+}
+
+impl RaceText {
+    pub fn word_supplier(&self) -> WordSupplierList {
+        WordSupplierList::new(self.words.clone(), self.line_based)
+    }
+    pub fn length(&self) -> usize {
+        text_length(&self.words)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Racer {
+    pub name: String,
+    /// Graphemes of the text typed so far.
+    pub position: usize,
+    pub wpm: f64,
+    /// Time it took to finish the text.
+    pub finished_ms: Option<u64>,
+    pub left: bool,
+}
+
+impl Racer {
+    fn new(name: &str) -> Self {
+        let name = name.trim().chars().take(MAX_NAME).collect::<String>();
+        Racer {
+            name: if name.is_empty() {
+                "racer".into()
+            } else {
+                name
+            },
+            position: 0,
+            wpm: 0f64,
+            finished_ms: None,
+            left: false,
+        }
+    }
+    /// Progress received from another racer, kept within the text.
+    fn set_progress(&mut self, position: usize, wpm: f64, finished_ms: Option<u64>, length: usize) {
+        self.position = position.min(length);
+        self.wpm = wpm;
+        self.finished_ms = finished_ms;
+    }
+}
+
+/// Messages exchanged between the host and its clients, one JSON object per
+/// line. Racers are identified by the number of their connection, the host
+/// itself is 0.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    Join {
+        name: String,
+    },
+    Welcome {
+        id: usize,
+        text: RaceText,
+    },
+    Refused {
+        reason: String,
+    },
+    /// Pairs rather than a map, as JSON object keys can't be numbers.
+    Racers {
+        racers: Vec<(usize, Racer)>,
+    },
+    Start {
+        countdown_ms: u64,
+    },
+    Progress {
+        id: usize,
+        position: usize,
+        wpm: f64,
+        finished_ms: Option<u64>,
+    },
+}
+
+enum Event {
+    Connected(usize, TcpStream),
+    Received(usize, Message),
+    Closed(usize),
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+fn receive(reader: &mut impl BufRead) -> io::Result<Option<Message>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Forwards every message of a connection until it closes.
+fn spawn_reader(id: usize, mut reader: BufReader<TcpStream>, events: Sender<Event>) {
+    thread::spawn(move || {
+        while let Ok(Some(message)) = receive(&mut reader) {
+            if events.send(Event::Received(id, message)).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(Event::Closed(id));
+    });
+}
+
+enum Link {
+    Host {
+        port: u16,
+        peers: BTreeMap<usize, TcpStream>,
+    },
+    Client {
+        address: String,
+        host: TcpStream,
+    },
+}
+
+pub enum LobbyAction {
+    Continue,
+    Start,
+    Leave,
+}
+
+/// A race on the local network, seen from one of its racers. The host relays
+/// the progress of every racer to all the others.
+pub struct Race {
+    link: Link,
+    events: Receiver<Event>,
+    pub text: RaceText,
+    pub racers: BTreeMap<usize, Racer>,
+    /// Id of the local racer.
+    pub me: usize,
+    start: Option<Instant>,
+    /// Problem with the connection to show to the user.
+    pub message: Option<String>,
+}
+
+impl Race {
+    pub fn host(port: u16, name: &str, text: RaceText) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let port = listener.local_addr()?.port();
+        let (sender, events) = channel();
+        thread::spawn(move || {
+            for (id, stream) in (1..).zip(listener.incoming()) {
+                let Ok(stream) = stream else { continue };
+                let Ok(writer) = stream.try_clone() else {
+                    continue;
+                };
+                if sender.send(Event::Connected(id, writer)).is_err() {
+                    return;
+                }
+                spawn_reader(id, BufReader::new(stream), sender.clone());
+            }
+        });
+        Ok(Race {
+            link: Link::Host {
+                port,
+                peers: BTreeMap::new(),
+            },
+            events,
+            text,
+            racers: BTreeMap::from([(0, Racer::new(name))]),
+            me: 0,
+            start: None,
+            message: None,
+        })
+    }
+
+    /// Connects to a host and waits for the text of the race.
+    pub fn join(address: &str, name: &str) -> io::Result<Self> {
+        let address = match address.contains(':') {
+            true => address.to_string(),
+            false => format!("{}:{}", address, DEFAULT_PORT),
+        };
+        let mut host = TcpStream::connect(&address)?;
+        send(&mut host, &Message::Join { name: name.into() })?;
+        host.set_read_timeout(Some(JOIN_TIMEOUT))?;
+        let mut reader = BufReader::new(host.try_clone()?);
+        let (me, text) = match receive(&mut reader)? {
+            Some(Message::Welcome { id, text }) => (id, text),
+            Some(Message::Refused { reason }) => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason))
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected answer from the host",
+                ))
+            }
+        };
+        host.set_read_timeout(None)?;
+        let (sender, events) = channel();
+        spawn_reader(0, reader, sender);
+        Ok(Race {
+            link: Link::Client { address, host },
+            events,
+            text,
+            racers: BTreeMap::from([(me, Racer::new(name))]),
+            me,
+            start: None,
+            message: None,
+        })
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.link, Link::Host { .. })
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        match self.link {
+            Link::Host { port, .. } => Some(port),
+            Link::Client { .. } => None,
+        }
+    }
+
+    fn broadcast(&mut self, message: &Message) {
+        if let Link::Host { peers, .. } = &mut self.link {
+            // Peers that break are dropped once their reader notices.
+            for (_, peer) in peers
+                .iter_mut()
+                .filter(|(id, _)| self.racers.contains_key(id))
+            {
+                let _ = send(peer, message);
+            }
+        }
+    }
+
+    fn broadcast_racers(&mut self) {
+        let racers = self.racers.clone().into_iter().collect();
+        self.broadcast(&Message::Racers { racers });
+    }
+
+    /// Handles everything received since the last call.
+    pub fn poll(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            if self.is_host() {
+                self.handle_host(event);
+            } else {
+                self.handle_client(event);
+            }
+        }
+    }
+
+    fn handle_host(&mut self, event: Event) {
+        let Link::Host { peers, .. } = &mut self.link else {
+            return;
+        };
+        match event {
+            Event::Connected(id, stream) => {
+                peers.insert(id, stream);
+            }
+            Event::Received(id, Message::Join { name }) => {
+                let Some(peer) = peers.get_mut(&id) else {
+                    return;
+                };
+                if self.start.is_some() {
+                    let reason = "the race has already started".to_string();
+                    let _ = send(peer, &Message::Refused { reason });
+                    let _ = peer.shutdown(Shutdown::Both);
+                    peers.remove(&id);
+                    return;
+                }
+                let text = self.text.clone();
+                if send(peer, &Message::Welcome { id, text }).is_ok() {
+                    self.racers.insert(id, Racer::new(&name));
+                    self.broadcast_racers();
+                }
+            }
+            Event::Received(
+                from,
+                Message::Progress {
+                    id,
+                    position,
+                    wpm,
+                    finished_ms,
+                },
+            ) if from == id => {
+                let length = self.text.length();
+                if let Some(racer) = self.racers.get_mut(&id) {
+                    racer.set_progress(position, wpm, finished_ms, length);
+                    let position = racer.position;
+                    self.broadcast(&Message::Progress {
+                        id,
+                        position,
+                        wpm,
+                        finished_ms,
+                    });
+                }
+            }
+            Event::Received(..) => {}
+            Event::Closed(id) => {
+                peers.remove(&id);
+                if let Some(racer) = self.racers.get_mut(&id) {
+                    racer.left = true;
+                    self.broadcast_racers();
+                }
+            }
+        }
+    }
+
+    fn handle_client(&mut self, event: Event) {
+        match event {
+            Event::Received(_, Message::Racers { racers }) => {
+                let length = self.text.length();
+                self.racers = racers
+                    .into_iter()
+                    .map(|(id, mut racer)| {
+                        racer.position = racer.position.min(length);
+                        (id, racer)
+                    })
+                    .collect()
+            }
+            Event::Received(_, Message::Start { countdown_ms }) => {
+                self.start = Some(Instant::now() + Duration::from_millis(countdown_ms));
+            }
+            Event::Received(
+                _,
+                Message::Progress {
+                    id,
+                    position,
+                    wpm,
+                    finished_ms,
+                },
+            ) if id != self.me => {
+                let length = self.text.length();
+                if let Some(racer) = self.racers.get_mut(&id) {
+                    racer.set_progress(position, wpm, finished_ms, length);
+                }
+            }
+            Event::Closed(_) => {
+                self.message = Some("The host has closed the race".into());
+                for (_, racer) in self.racers.iter_mut().filter(|(&id, _)| id != self.me) {
+                    racer.left = true;
+                }
+            }
+            Event::Received(..) | Event::Connected(..) => {}
+        }
+    }
+
+    /// Starts the countdown for everyone, only the host can do this.
+    pub fn start(&mut self) {
+        if !self.is_host() || self.start.is_some() {
+            return;
+        }
+        self.start = Some(Instant::now() + COUNTDOWN);
+        self.broadcast(&Message::Start {
+            countdown_ms: COUNTDOWN.as_millis() as u64,
+        });
+    }
+
+    /// Time left until the race starts, once the host has started it.
+    pub fn countdown(&self) -> Option<Duration> {
+        self.start
+            .map(|start| start.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn started(&self) -> bool {
+        self.countdown() == Some(Duration::ZERO)
+    }
+
+    /// Shares the local racer's progress with the others.
+    pub fn update(&mut self, position: usize, wpm: f64, finished: Option<Duration>) {
+        let finished_ms = finished.map(|time| time.as_millis() as u64);
+        let Some(racer) = self.racers.get_mut(&self.me) else {
+            return;
+        };
+        if racer.position == position && racer.finished_ms == finished_ms {
+            return;
+        }
+        racer.position = position;
+        racer.wpm = wpm;
+        racer.finished_ms = finished_ms;
+        let message = Message::Progress {
+            id: self.me,
+            position,
+            wpm,
+            finished_ms,
+        };
+        match &mut self.link {
+            Link::Host { .. } => self.broadcast(&message),
+            Link::Client { host, .. } => {
+                if send(host, &message).is_err() {
+                    self.message = Some("Lost the connection to the host".into());
+                }
+            }
+        }
+    }
+
+    /// Racers that finished by time, then the others by how far they got.
+    pub fn ranking(&self) -> Vec<(usize, &Racer)> {
+        let mut ranking = self
+            .racers
+            .iter()
+            .map(|(&id, r)| (id, r))
+            .collect::<Vec<_>>();
+        ranking.sort_by_key(|(_, racer)| match racer.finished_ms {
+            Some(ms) => (0, ms),
+            None => (1, u64::MAX - racer.position as u64),
+        });
+        ranking
+    }
+
+    pub fn handle_lobby_events(&mut self) -> io::Result<LobbyAction> {
+        self.poll();
+        if self.start.is_some() {
+            return Ok(LobbyAction::Start);
+        }
+        Ok(match read_key()? {
+            Some(KeyCode::Esc) => LobbyAction::Leave,
+            Some(KeyCode::Enter) if self.is_host() => {
+                self.start();
+                LobbyAction::Start
+            }
+            _ => LobbyAction::Continue,
+        })
+    }
+}
+
+mod widget {
+    use ratatui::{prelude::*, widgets::*};
+
+    use super::{Race, Racer};
+
+    fn label(racer: &Racer) -> String {
+        let status = match (racer.finished_ms, racer.left) {
+            (Some(ms), _) => format!("{:.0} wpm {:.1} s", racer.wpm, ms as f64 / 1000f64),
+            (None, true) => "left".to_string(),
+            (None, false) => format!("{:.0} wpm", racer.wpm),
+        };
+        format!("{:<16} {:>14}", racer.name, status)
+    }
+
+    /// Progress bars of every racer, one per line.
+    pub struct RaceProgress<'a>(pub &'a Race);
+
+    impl<'a> Widget for RaceProgress<'a> {
+        fn render(self, area: Rect, buf: &mut Buffer) {
+            let race = self.0;
+            let length = race.text.length().max(1);
+            let rows = Layout::vertical(vec![Constraint::Length(1); race.racers.len()]).split(area);
+            for ((id, racer), row) in race.racers.iter().zip(rows.iter()) {
+                let style = match *id == race.me {
+                    true => Style::new().bold(),
+                    false => Style::new(),
+                };
+                LineGauge::default()
+                    .ratio((racer.position as f64 / length as f64).min(1f64))
+                    .label(Span::styled(label(racer), style))
+                    .filled_style(style.cyan())
+                    .render(*row, buf);
+            }
+        }
+    }
+
+    /// Final standings of a race.
+    pub struct RaceRanking<'a>(pub &'a Race);
+
+    impl<'a> Widget for RaceRanking<'a> {
+        fn render(self, area: Rect, buf: &mut Buffer) {
+            let race = self.0;
+            let length = race.text.length().max(1);
+            let rows = race
+                .ranking()
+                .into_iter()
+                .enumerate()
+                .map(|(i, (id, racer))| {
+                    let place = match racer.finished_ms {
+                        Some(_) => format!("{}.", i + 1),
+                        None => "-".to_string(),
+                    };
+                    let result = match (racer.finished_ms, racer.left) {
+                        (Some(ms), _) => format!("{:.1} s", ms as f64 / 1000f64),
+                        (None, true) => "left".to_string(),
+                        (None, false) => format!("{}%", racer.position * 100 / length),
+                    };
+                    let row = Row::new([
+                        place,
+                        racer.name.clone(),
+                        format!("{:.0} wpm", racer.wpm),
+                        result,
+                    ]);
+                    match id == race.me {
+                        true => row.bold(),
+                        false => row,
+                    }
+                });
+            let widths = [
+                Constraint::Length(3),
+                Constraint::Length(16),
+                Constraint::Length(8),
+                Constraint::Length(8),
+            ];
+            Widget::render(
+                Table::new(rows, widths).block(Block::bordered().title("Ranking")),
+                area,
+                buf,
+            );
+        }
+    }
+
+    /// Waiting room before the host starts the race.
+    impl Widget for &Race {
+        fn render(self, area: Rect, buf: &mut Buffer) {
+            use Constraint::*;
+            let [title, hint, _, racers, message] =
+                Layout::vertical([Length(1), Length(1), Length(1), Fill(1), Length(1)]).areas(area);
+            let title_text = match &self.link {
+                super::Link::Host { port, .. } => format!("Hosting a race on port {}", port),
+                super::Link::Client { address, .. } => format!("Joined the race at {}", address),
+            };
+            Line::raw(title_text).bold().centered().render(title, buf);
+            let hint_text = match self.is_host() {
+                true => "Press Enter to start the race, Esc to leave",
+                false => "Waiting for the host to start, Esc to leave",
+            };
+            Line::raw(hint_text).centered().render(hint, buf);
+            let names = self.racers.iter().map(|(&id, racer)| {
+                let line = Line::raw(match (id == self.me, racer.left) {
+                    (true, _) => format!("{} (you)", racer.name),
+                    (false, true) => format!("{} (left)", racer.name),
+                    (false, false) => racer.name.clone(),
+                });
+                line.centered()
+            });
+            Text::from_iter(names).render(racers, buf);
+            if let Some(text) = &self.message {
+                Line::raw(text.as_str())
+                    .red()
+                    .centered()
+                    .render(message, buf);
+            }
+        }
+    }
+}
+
+pub use widget::{RaceProgress, RaceRanking};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn wait_for(race: &mut Race, done: impl Fn(&Race) -> bool) {
+        for _ in 0..500 {
+            race.poll();
+            if done(race) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("race did not reach the expected state");
+    }
+
+    #[test]
+    fn racers_share_progress_on_localhost() {
+        let text = RaceText {
+            words: vec!["ab".to_string(), "cd".to_string()],
+            line_based: false,
+            skip_indent: false,
+            source: None,
+        };
+        let mut host = Race::host(0, "host", text.clone()).unwrap();
+        let address = format!("127.0.0.1:{}", host.port().unwrap());
+        let guest = thread::spawn(move || Race::join(&address, "guest").unwrap());
+        wait_for(&mut host, |race| race.racers.len() == 2);
+        let mut guest = guest.join().unwrap();
+        assert_eq!(guest.text, text);
+
+        host.start();
+        wait_for(&mut guest, |race| {
+            race.countdown().is_some() && race.racers.len() == 2
+        });
+        // Positions past the end of the text are clamped to it.
+        guest.update(usize::MAX, 60f64, Some(Duration::from_secs(2)));
+        host.update(3, 40f64, None);
+        wait_for(&mut host, |race| {
+            race.racers[&guest.me].finished_ms.is_some()
+        });
+        assert_eq!(host.racers[&guest.me].position, 5);
+        wait_for(&mut guest, |race| race.racers[&0].position == 3);
+        let ranking = host.ranking();
+        assert_eq!(ranking[0].1.name, "guest");
+        assert_eq!(ranking[1].1.name, "host");
+    }
+}
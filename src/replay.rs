@@ -0,0 +1,153 @@
+use std::{fs, io, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    langs::{text_length, WordSupplier},
+    profile::Profile,
+    text::TextManager,
+};
+
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Input that changes the typed text. Enter is recorded as a `\n` char.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keystroke {
+    Char(char),
+    Backspace,
+    DeleteWord,
+}
+
+impl Keystroke {
+    pub fn is_deletion(self) -> bool {
+        matches!(self, Keystroke::Backspace | Keystroke::DeleteWord)
+    }
+    pub fn apply<Ws: WordSupplier>(self, text_manager: &mut TextManager<Ws>) {
+        match self {
+            Keystroke::Char(c) => text_manager.handle_char(c),
+            Keystroke::Backspace => text_manager.handle_backspace(),
+            Keystroke::DeleteWord => text_manager.handle_delete_word(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimedKeystroke {
+    /// Milliseconds since the first keystroke.
+    pub ms: u64,
+    pub key: Keystroke,
+}
+
+/// Everything needed to play a finished test back: the text as it was
+/// generated and every keystroke with its time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyLog {
+    pub version: u32,
+    pub timestamp: u64,
+    pub words: Vec<String>,
+    pub line_based: bool,
+    pub skip_indent: bool,
+    pub keys: Vec<TimedKeystroke>,
+}
+
+/// Words of a recorded text, handed out exactly as they were generated.
+pub struct WordSupplierList {
+    words: std::vec::IntoIter<String>,
+    line_based: bool,
+}
+
+impl WordSupplierList {
+    pub fn new(words: Vec<String>, line_based: bool) -> Self {
+        WordSupplierList {
+            words: words.into_iter(),
+            line_based,
+        }
+    }
+}
+
+impl WordSupplier for WordSupplierList {
+    fn get_word(&mut self) -> Option<String> {
+        self.words.next()
+    }
+    fn line_based(&self) -> bool {
+        self.line_based
+    }
+}
+
+impl KeyLog {
+    pub fn word_supplier(&self) -> WordSupplierList {
+        WordSupplierList::new(self.words.clone(), self.line_based)
+    }
+    /// Number of graphemes in the recorded text, counting separators.
+    pub fn length(&self) -> usize {
+        text_length(&self.words)
+    }
+    pub fn text_manager(&self) -> TextManager<WordSupplierList> {
+        let mut text_manager = TextManager::new(self.word_supplier());
+        text_manager.set_skip_indent(self.skip_indent);
+        text_manager
+    }
+}
+
+/// Records keystrokes as they are applied to a running test.
+#[derive(Default)]
+pub struct KeyRecorder {
+    keys: Vec<TimedKeystroke>,
+}
+
+impl KeyRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Records `key`, typed `elapsed` after the first keystroke.
+    pub fn record(&mut self, elapsed: Duration, key: Keystroke) {
+        self.keys.push(TimedKeystroke {
+            ms: elapsed.as_millis() as u64,
+            key,
+        });
+    }
+    pub fn finish<Ws: WordSupplier>(
+        &self,
+        text_manager: &TextManager<Ws>,
+        timestamp: u64,
+    ) -> KeyLog {
+        KeyLog {
+            version: REPLAY_VERSION,
+            timestamp,
+            words: text_manager.words(),
+            line_based: text_manager.line_based(),
+            skip_indent: text_manager.skip_indent(),
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+pub fn replays_dir(profile: &Profile) -> io::Result<PathBuf> {
+    Ok(profile.data_dir()?.join("replays"))
+}
+
+pub fn replay_path(profile: &Profile, timestamp: u64) -> io::Result<PathBuf> {
+    Ok(replays_dir(profile)?.join(format!("{}.json", timestamp)))
+}
+
+pub fn save_replay(profile: &Profile, log: &KeyLog) -> io::Result<PathBuf> {
+    let path = replay_path(profile, log.timestamp)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_string(log)?)?;
+    Ok(path)
+}
+
+pub fn load_replay(profile: &Profile, timestamp: u64) -> io::Result<KeyLog> {
+    let buf = fs::read_to_string(replay_path(profile, timestamp)?)?;
+    let log: KeyLog = serde_json::from_str(&buf)?;
+    if log.version > REPLAY_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "replay was saved by a newer version",
+        ));
+    }
+    Ok(log)
+}
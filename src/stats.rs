@@ -0,0 +1,312 @@
+use std::{collections::HashSet, fs, io};
+
+use ratatui::crossterm::event::KeyCode;
+
+use crate::{
+    app::GameOptions,
+    export::{export_to_file, ExportFormat},
+    history::{load_history, GameResult},
+    input::read_key_block,
+    keys::{load_key_stats, KeyStats},
+    profile::Profile,
+    replay::{load_replay, replays_dir, KeyLog},
+};
+
+/// Best result for every combination of test conditions, sorted by mode.
+pub fn personal_bests(history: &[GameResult]) -> Vec<&GameResult> {
+    let mut bests: Vec<&GameResult> = vec![];
+    for result in history {
+        match bests.iter_mut().find(|best| best.comparable(result)) {
+            Some(best) if best.wpm < result.wpm => *best = result,
+            Some(_) => {}
+            None => bests.push(result),
+        }
+    }
+    bests.sort_by(|a, b| a.label().cmp(&b.label()).then(a.language.cmp(&b.language)));
+    bests
+}
+
+/// Whether `result` beats every earlier comparable result.
+pub fn is_personal_best(history: &[GameResult], result: &GameResult) -> bool {
+    history
+        .iter()
+        .filter(|r| r.comparable(result))
+        .all(|r| r.wpm < result.wpm)
+}
+
+/// Average WPM and accuracy over the last `count` tests.
+pub fn recent_average(history: &[GameResult], count: usize) -> Option<(f64, f64)> {
+    let recent = &history[history.len().saturating_sub(count)..];
+    if recent.is_empty() {
+        return None;
+    }
+    let len = recent.len() as f64;
+    let wpm = recent.iter().map(|r| r.wpm).sum::<f64>() / len;
+    let acc = recent.iter().map(|r| r.acc).sum::<f64>() / len;
+    Some((wpm, acc))
+}
+
+/// Timestamps of tests with a saved replay.
+fn saved_replays(profile: &Profile) -> HashSet<u64> {
+    let Ok(dir) = replays_dir(profile) else {
+        return HashSet::new();
+    };
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.path().file_stem()?.to_str()?.parse().ok())
+        .collect()
+}
+
+/// Saved replay of the fastest result matching `options`, with the seed its
+/// text was generated from.
+pub fn best_replay(
+    profile: &Profile,
+    history: &[GameResult],
+    options: &GameOptions,
+) -> Option<(KeyLog, Option<u64>)> {
+    let mut results = history
+        .iter()
+        .filter(|r| r.matches_options(options))
+        .collect::<Vec<_>>();
+    results.sort_by(|a, b| b.wpm.total_cmp(&a.wpm));
+    results
+        .into_iter()
+        .find_map(|r| Some((load_replay(profile, r.timestamp).ok()?, r.seed)))
+}
+
+pub enum StatsAction {
+    Continue,
+    Back,
+    Replay(KeyLog),
+}
+
+pub struct StatsScreen {
+    profile: Profile,
+    history: io::Result<Vec<GameResult>>,
+    keys: io::Result<KeyStats>,
+    replays: HashSet<u64>,
+    /// Selected row of the recent tests, newest first.
+    selected: usize,
+    message: Option<String>,
+}
+
+impl StatsScreen {
+    pub fn new(profile: &Profile) -> Self {
+        StatsScreen {
+            profile: profile.clone(),
+            history: load_history(profile),
+            keys: load_key_stats(profile),
+            replays: saved_replays(profile),
+            selected: 0,
+            message: None,
+        }
+    }
+    fn selected_result(&self) -> Option<&GameResult> {
+        let history = self.history.as_ref().ok()?;
+        history.iter().rev().nth(self.selected)
+    }
+    pub fn handle_events(&mut self) -> io::Result<StatsAction> {
+        let count = self.history.as_ref().map_or(0, |history| history.len());
+        match read_key_block()? {
+            KeyCode::Esc => return Ok(StatsAction::Back),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+            KeyCode::Enter => {
+                if let Some(result) = self.selected_result() {
+                    match load_replay(&self.profile, result.timestamp) {
+                        Ok(log) => return Ok(StatsAction::Replay(log)),
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                            self.message = Some("No replay saved for this test".to_string())
+                        }
+                        Err(e) => self.message = Some(format!("Could not open replay: {}", e)),
+                    }
+                }
+            }
+            KeyCode::Char(c @ ('c' | 'j')) => {
+                let format = if c == 'c' {
+                    ExportFormat::Csv
+                } else {
+                    ExportFormat::Json
+                };
+                if let Ok(history) = &self.history {
+                    self.message = Some(match export_to_file(&self.profile, history, format) {
+                        Ok(path) => format!("Exported to {}", path.display()),
+                        Err(e) => format!("Could not export: {}", e),
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(StatsAction::Continue)
+    }
+}
+
+mod widget {
+    use ratatui::{prelude::*, widgets::*};
+
+    use super::{personal_bests, recent_average, StatsScreen};
+    use crate::heatmap::{KeyboardHeatmap, HEATMAP_HEIGHT};
+
+    impl Widget for &StatsScreen {
+        fn render(self, area: Rect, buf: &mut Buffer) {
+            use Constraint::*;
+            let [top, message_line, tables, heatmap, _, bottom] = Layout::vertical([
+                Length(1),
+                Length(1),
+                Length(8),
+                Length(HEATMAP_HEIGHT),
+                Length(1),
+                Fill(1),
+            ])
+            .areas(area);
+            Line::raw(format!(
+                "Statistics of {} - Up/Down select a test, Enter replays it, c/j export CSV/JSON, Esc returns",
+                self.profile.name()
+            ))
+            .bold()
+            .centered()
+            .render(top, buf);
+
+            let history = match &self.history {
+                Ok(history) => history,
+                Err(e) => {
+                    Line::raw(format!("Could not read history: {}", e))
+                        .red()
+                        .centered()
+                        .render(tables, buf);
+                    return;
+                }
+            };
+            if history.is_empty() {
+                Line::raw("No finished tests yet")
+                    .centered()
+                    .render(tables, buf);
+                return;
+            }
+
+            let [bests_area, averages_area] =
+                Layout::horizontal([Percentage(60), Percentage(40)]).areas(tables);
+            let rows = personal_bests(history).into_iter().map(|best| {
+                Row::new(vec![
+                    best.label(),
+                    best.language.clone(),
+                    format!("{:.02}", best.wpm),
+                    format!("{:.02}", best.acc),
+                ])
+            });
+            let bests = Table::new(rows, [Fill(2), Fill(2), Fill(1), Fill(1)])
+                .header(Row::new(vec!["Mode", "Language", "WPM", "Acc"]).bold())
+                .block(Block::bordered().title("Personal bests"));
+            Widget::render(bests, bests_area, buf);
+
+            let averages = [10, 100].map(|count| {
+                let (wpm, acc) = recent_average(history, count).unwrap_or_default();
+                Row::new(vec![
+                    format!("last {}", count),
+                    format!("{:.02}", wpm),
+                    format!("{:.02}", acc),
+                ])
+            });
+            let averages = Table::new(averages, [Fill(2), Fill(1), Fill(1)])
+                .header(Row::new(vec!["Average", "WPM", "Acc"]).bold())
+                .block(Block::bordered().title(format!("{} tests", history.len())));
+            Widget::render(averages, averages_area, buf);
+
+            match &self.keys {
+                Ok(keys) => KeyboardHeatmap::new(keys).render(heatmap, buf),
+                Err(e) => Line::raw(format!("Could not read key statistics: {}", e))
+                    .red()
+                    .centered()
+                    .render(heatmap, buf),
+            }
+
+            if let Some(message) = &self.message {
+                Line::raw(message.as_str())
+                    .yellow()
+                    .centered()
+                    .render(message_line, buf);
+            }
+
+            let [chart, recent] = Layout::horizontal([Fill(1), Length(44)]).areas(bottom);
+            let rows = history.iter().rev().map(|r| {
+                let replay = if self.replays.contains(&r.timestamp) {
+                    "▶"
+                } else {
+                    ""
+                };
+                Row::new(vec![
+                    r.label(),
+                    format!("{:.02}", r.wpm),
+                    format!("{:.02}", r.acc),
+                    replay.to_string(),
+                ])
+            });
+            let recent_table = Table::new(rows, [Fill(1), Length(7), Length(7), Length(1)])
+                .header(Row::new(vec!["Recent", "WPM", "Acc", ""]).bold())
+                .highlight_style(Style::new().reversed())
+                .block(Block::bordered().title("Tests"));
+            let mut state = TableState::default().with_selected(Some(self.selected));
+            StatefulWidget::render(recent_table, recent, buf, &mut state);
+
+            let points = history
+                .iter()
+                .enumerate()
+                .map(|(i, r)| ((i + 1) as f64, r.wpm))
+                .collect::<Vec<_>>();
+            let max_wpm = points.iter().map(|p| p.1).fold(0f64, f64::max).ceil();
+            let dataset = Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .blue()
+                .data(&points);
+            Chart::new(vec![dataset])
+                .block(Block::bordered().title("WPM over time"))
+                .x_axis(
+                    Axis::default()
+                        .title("test")
+                        .bounds([1f64, points.len().max(2) as f64])
+                        .labels(vec!["1".into(), points.len().to_string().into()]),
+                )
+                .y_axis(
+                    Axis::default()
+                        .title("wpm")
+                        .bounds([0f64, max_wpm.max(1f64)])
+                        .labels(vec!["0".into(), format!("{}", max_wpm).into()]),
+                )
+                .render(chart, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history::ResultMode;
+
+    fn result(time: u32, wpm: f64) -> GameResult {
+        GameResult {
+            duration: time as f64,
+            wpm,
+            acc: 100.0,
+            ..GameResult::sample(ResultMode::Time { time })
+        }
+    }
+
+    #[test]
+    fn bests_per_mode() {
+        let history = vec![result(30, 50.0), result(60, 40.0), result(30, 70.0)];
+        let bests = personal_bests(&history);
+        assert_eq!(bests.len(), 2);
+        assert_eq!(bests[0].wpm, 70.0);
+        assert_eq!(bests[1].wpm, 40.0);
+        assert!(is_personal_best(&history, &result(60, 45.0)));
+        assert!(!is_personal_best(&history, &result(30, 60.0)));
+        let punctuated = GameResult {
+            punctuation: true,
+            ..result(30, 10.0)
+        };
+        assert!(is_personal_best(&history, &punctuated));
+        assert_eq!(recent_average(&history, 2), Some((55.0, 100.0)));
+    }
+}
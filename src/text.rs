@@ -1,41 +1,183 @@
-use crate::langs::{WordSupplier, WordSupplierBasic, WordSupplierRandomized};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    keys::KeyStats,
+    langs::{WordSupplier, WordSupplierBasic, WordSupplierRandomized},
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CharBreakdown {
+    pub correct: usize,
+    pub incorrect: usize,
+    /// Letters typed where a space was expected. Text is typed one grapheme
+    /// per position, so the rest of the text doesn't shift.
+    pub extra: usize,
+    /// Spaces typed where a letter was expected. The rest of the word isn't
+    /// skipped, it is still typed position by position.
+    pub missed: usize,
+    /// Mistakes fixed with backspace.
+    pub corrected: usize,
+}
+
+/// Text is handled as extended grapheme clusters in NFC, so a letter with
+/// combining marks is typed, compared and deleted as a single unit.
+pub type Grapheme = String;
+
+/// Whether `typed` is an incomplete input of `expected`, compared in NFD so
+/// combining marks typed separately still match.
+fn is_prefix(typed: &str, expected: &str) -> bool {
+    typed != expected
+        && expected
+            .nfd()
+            .collect::<String>()
+            .starts_with(&typed.nfd().collect::<String>())
+}
+
+pub fn display_width(graphemes: &[Grapheme]) -> usize {
+    graphemes.iter().map(|g| g.width()).sum()
+}
+
+fn graphemes(text: &str) -> impl Iterator<Item = Grapheme> + '_ {
+    text.graphemes(true).map(|g| g.nfc().collect())
+}
 
 pub struct TextManager<Ws: WordSupplier> {
     word_supplier: Ws,
-    text: Vec<char>,
+    text: Vec<Grapheme>,
+    /// Display width of `text[..i]` at index `i`.
+    columns: Vec<usize>,
     word_index: Vec<usize>,
-    user_text: Vec<char>,
+    user_text: Vec<Grapheme>,
+    /// Start of a grapheme typed as several chars, e.g. a base letter waiting
+    /// for its combining mark.
+    pending: String,
     correct: usize,
     typed: usize,
+    errors: usize,
+    corrected: usize,
+    exhausted: bool,
+    /// Words are whole lines, each shown on its own row and ended by Enter.
+    line_breaks: bool,
+    skip_indent: bool,
+    /// Positions of indentation filled in automatically, in ascending order.
+    skipped: Vec<usize>,
+    key_stats: KeyStats,
+    /// Time of the keystrokes handled next, see `set_time`.
+    time: Duration,
+    last_key: Option<Duration>,
 }
 
 impl<Ws: WordSupplier> TextManager<Ws> {
     pub fn new(word_supplier: Ws) -> Self {
         TextManager {
+            line_breaks: word_supplier.line_based(),
             word_supplier,
             text: vec![],
+            columns: vec![0],
             word_index: vec![],
             user_text: vec![],
+            pending: String::new(),
             correct: 0,
             typed: 0,
+            errors: 0,
+            corrected: 0,
+            exhausted: false,
+            skip_indent: false,
+            skipped: vec![],
+            key_stats: KeyStats::default(),
+            time: Duration::ZERO,
+            last_key: None,
         }
     }
+    /// Fill in leading indentation of lines instead of making it typed.
+    pub fn set_skip_indent(&mut self, skip_indent: bool) {
+        self.skip_indent = skip_indent;
+    }
+    /// Sets the time of the keystrokes handled next, since any fixed start,
+    /// to measure the latency of every key.
+    pub fn set_time(&mut self, time: Duration) {
+        self.time = time;
+    }
+    pub fn skip_indent(&self) -> bool {
+        self.skip_indent
+    }
+    pub fn line_based(&self) -> bool {
+        self.line_breaks
+    }
+    fn separator(&self) -> Grapheme {
+        if self.line_breaks { "\n" } else { " " }.to_string()
+    }
+    fn push_grapheme(&mut self, grapheme: Grapheme) {
+        let column = self.columns.last().unwrap() + grapheme.width();
+        self.text.push(grapheme);
+        self.columns.push(column);
+    }
+    fn has_word(&mut self, index: usize) -> bool {
+        while !self.exhausted && self.word_index.len() <= index {
+            self.word_supplier.observe(&self.key_stats);
+            match self.word_supplier.get_word() {
+                Some(word) => {
+                    self.word_index.push(self.text.len());
+                    for grapheme in graphemes(&word) {
+                        self.push_grapheme(grapheme);
+                    }
+                    self.push_grapheme(self.separator());
+                }
+                None => self.exhausted = true,
+            }
+        }
+        index < self.word_index.len()
+    }
     fn begin_of_word(&mut self, index: usize) -> usize {
-        while self.word_index.len() <= index {
-            self.word_index.push(self.text.len());
-            self.text.extend(self.word_supplier.get_word());
-            self.text.push(' ');
+        if self.has_word(index) {
+            self.word_index[index]
+        } else {
+            self.text.len()
+        }
+    }
+    /// Start of the first word after the grapheme at `pos`, or the end of the
+    /// text once the supplier runs out.
+    fn next_word_begin(&mut self, pos: usize) -> usize {
+        loop {
+            let index = self.word_index.partition_point(|&begin| begin <= pos);
+            if !self.has_word(index) {
+                return self.text.len();
+            }
+            if self.word_index[index] > pos {
+                return self.word_index[index];
+            }
         }
-        *self.word_index.get(index).unwrap()
     }
-    fn next_line_begin(&mut self, mut ind: usize, width: usize) -> usize {
-        let end = self.begin_of_word(ind) + width;
-        while self.begin_of_word(ind + 1) <= end {
-            ind += 1;
+    /// Start of the line after the one starting at grapheme `begin`. Words
+    /// wider than the line are split, so the view keeps advancing.
+    fn next_line_begin(&mut self, begin: usize, width: usize) -> usize {
+        if self.line_breaks {
+            return self.next_word_begin(begin);
         }
-        ind
+        let end = self.columns[begin] + width;
+        let mut next = begin;
+        loop {
+            let word = self.next_word_begin(next);
+            if word == next || self.columns[word] > end {
+                break;
+            }
+            next = word;
+        }
+        if next == begin {
+            let word_end = self.next_word_begin(begin);
+            while next < word_end && self.columns[next + 1] <= end {
+                next += 1;
+            }
+        }
+        next
     }
-    fn widget_data(&mut self, width: usize) -> WidgetData {
+    /// Lines around the cursor when the text is wrapped at `width` columns.
+    pub fn widget_data(&mut self, width: usize) -> WidgetData<'_> {
         let mut begin = [0usize; 4];
         begin[1] = self.next_line_begin(begin[0], width);
         if begin[0] == begin[1] {
@@ -44,74 +186,216 @@ impl<Ws: WordSupplier> TextManager<Ws> {
         begin[2] = self.next_line_begin(begin[1], width);
         begin[3] = self.next_line_begin(begin[2], width);
         let ind = self.user_text.len();
-        if self.begin_of_word(begin[0]) <= ind && ind < self.begin_of_word(begin[1]) {
-            let inds = begin.map(|i| self.begin_of_word(i));
+        if ind < begin[1] {
             return WidgetData {
-                prev_line: &self.text[inds[0]..inds[1]],
-                line: &self.text[inds[1]..inds[2]],
-                next_line: &self.text[inds[2]..inds[3]],
-                prev_user_text: &self.user_text[inds[0]..],
+                start: begin[0],
+                prev_line: &self.text[begin[0]..begin[1]],
+                line: &self.text[begin[1]..begin[2]],
+                next_line: &self.text[begin[2]..begin[3]],
+                prev_user_text: &self.user_text[begin[0]..],
                 user_text: &[],
             };
         }
-        while !(self.begin_of_word(begin[1]) <= ind && ind < self.begin_of_word(begin[2])) {
+        while begin[1] != begin[2] && !(begin[1] <= ind && ind < begin[2]) {
             begin[0] = begin[1];
             begin[1] = begin[2];
             begin[2] = begin[3];
             begin[3] = self.next_line_begin(begin[3], width);
         }
-        let inds = begin.map(|i| self.begin_of_word(i));
         WidgetData {
-            prev_line: &self.text[inds[0]..inds[1]],
-            line: &self.text[inds[1]..inds[2]],
-            next_line: &self.text[inds[2]..inds[3]],
-            prev_user_text: &self.user_text[inds[0]..inds[1]],
-            user_text: &self.user_text[inds[1]..],
+            start: begin[0],
+            prev_line: &self.text[begin[0]..begin[1]],
+            line: &self.text[begin[1]..begin[2]],
+            next_line: &self.text[begin[2]..begin[3]],
+            prev_user_text: &self.user_text[begin[0]..begin[1]],
+            user_text: &self.user_text[begin[1]..],
         }
     }
     pub fn handle_char(&mut self, u: char) {
-        if let Some(&c) = self.text.get(self.user_text.len()) {
-            self.user_text.push(u);
-            if c == u {
-                self.correct += 1;
+        if self.text_finished() || u == '\n' && !self.line_breaks {
+            return;
+        }
+        let mut words = self.word_index.len();
+        while self.user_text.len() >= self.text.len() && self.has_word(words) {
+            words += 1;
+        }
+        self.skip_indentation();
+        let Some(expected) = self.text.get(self.user_text.len()) else {
+            return;
+        };
+        let pending = std::mem::take(&mut self.pending);
+        let typed: Grapheme = pending.chars().chain([u]).nfc().collect();
+        if &typed == expected || typed.chars().count() == 1 && !is_prefix(&typed, expected) {
+            self.commit(typed);
+        } else if is_prefix(&typed, expected) {
+            self.pending = typed;
+        } else {
+            // The pending start was wrong: count it as a mistake on its own
+            // and treat the new char as the start of the next grapheme.
+            self.commit(pending.nfc().collect());
+            self.handle_char(u);
+        }
+    }
+    fn commit(&mut self, typed: Grapheme) {
+        let position = self.user_text.len();
+        let expected = &self.text[position];
+        let correct = typed == *expected;
+        if correct {
+            self.correct += 1;
+        } else {
+            self.errors += 1;
+        }
+        let prev = position.checked_sub(1).map(|i| self.text[i].as_str());
+        let latency = self.last_key.map(|last| self.time.saturating_sub(last));
+        self.key_stats.record(prev, expected, &typed, latency);
+        self.last_key = Some(self.time);
+        self.typed += 1;
+        self.user_text.push(typed);
+        self.skip_indentation();
+        // Ask for the next word at the end of the loaded text, so
+        // `text_finished` knows when the supplier has run out.
+        if self.user_text.len() + 1 >= self.text.len() {
+            self.has_word(self.word_index.len());
+        }
+    }
+    fn skip_indentation(&mut self) {
+        while self.skip_indent {
+            let position = self.user_text.len();
+            let line_start = position == 0
+                || self.text[position - 1] == "\n"
+                || self.skipped.last() == Some(&(position - 1));
+            match self.text.get(position) {
+                Some(c) if line_start && c == " " => {
+                    self.user_text.push(c.clone());
+                    self.skipped.push(position);
+                }
+                _ => return,
             }
-            self.typed += 1;
         }
     }
     pub fn handle_backspace(&mut self) {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            return;
+        }
+        while !self.user_text.is_empty() && self.skipped.last() == Some(&(self.user_text.len() - 1))
+        {
+            self.user_text.pop();
+            self.skipped.pop();
+        }
         if let Some(u) = self.user_text.pop() {
-            if let Some(&c) = self.text.get(self.user_text.len()) {
-                if c == u {
+            if let Some(c) = self.text.get(self.user_text.len()) {
+                if *c == u {
                     self.correct -= 1;
+                } else {
+                    self.corrected += 1;
                 }
             }
         }
     }
+    /// Deletes back to the start of the word under the cursor, or of the
+    /// previous word when the cursor is right after a separator.
+    pub fn handle_delete_word(&mut self) {
+        let separator = |g: &Grapheme| g == " " || g == "\n";
+        self.pending.clear();
+        while self.position() > 0 && separator(&self.text[self.position() - 1]) {
+            self.handle_backspace();
+        }
+        while self.position() > 0 && !separator(&self.text[self.position() - 1]) {
+            self.handle_backspace();
+        }
+    }
+    /// Words supplied so far, without separators.
+    pub fn words(&self) -> Vec<String> {
+        let ends = self
+            .word_index
+            .iter()
+            .skip(1)
+            .copied()
+            .chain([self.text.len()]);
+        self.word_index
+            .iter()
+            .zip(ends)
+            .map(|(&begin, end)| self.text[begin..end - 1].concat())
+            .collect()
+    }
+    /// Percentage of keystrokes that were correct when typed, so mistakes count
+    /// against accuracy even after they are fixed.
     pub fn accuracy(&self) -> f64 {
         if self.typed == 0 {
             return 0f64;
         }
-        self.correct as f64 * 100f64 / self.typed as f64
+        (self.typed - self.errors) as f64 * 100f64 / self.typed as f64
+    }
+    pub fn breakdown(&self) -> CharBreakdown {
+        let mut breakdown = CharBreakdown {
+            corrected: self.corrected,
+            ..Default::default()
+        };
+        for (i, (c, u)) in self.text.iter().zip(&self.user_text).enumerate() {
+            match (c.as_str(), u.as_str()) {
+                _ if self.skipped.binary_search(&i).is_ok() => {}
+                _ if c == u => breakdown.correct += 1,
+                (" " | "\n", _) => breakdown.extra += 1,
+                (_, " " | "\n") => breakdown.missed += 1,
+                _ => breakdown.incorrect += 1,
+            }
+        }
+        breakdown
+    }
+    /// Accuracy and latency per key for keystrokes made in this text.
+    pub fn key_stats(&self) -> &KeyStats {
+        &self.key_stats
     }
     pub fn correct(&self) -> usize {
         self.correct
     }
+    pub fn keystrokes(&self) -> usize {
+        self.typed
+    }
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+    pub fn words_completed(&mut self) -> usize {
+        let typed = self.user_text.len();
+        let mut words = 0;
+        while self.has_word(words) && self.begin_of_word(words + 1) - 1 <= typed {
+            words += 1;
+        }
+        words
+    }
+    /// Graphemes typed by the user, not counting skipped indentation.
+    pub fn typed(&self) -> usize {
+        self.user_text.len() - self.skipped.len()
+    }
+    /// Position of the cursor in the text.
+    pub fn position(&self) -> usize {
+        self.user_text.len()
+    }
+    /// True once the supplier ran out and every character but the trailing space is typed.
+    pub fn text_finished(&self) -> bool {
+        self.exhausted && self.user_text.len() + 1 >= self.text.len()
+    }
 }
 
 pub type TextManagerBasic = TextManager<WordSupplierBasic>;
 pub type TextManagerLang = TextManager<WordSupplierRandomized>;
+pub type TextManagerDyn = TextManager<Box<dyn WordSupplier>>;
 
-struct WidgetData<'a> {
-    prev_line: &'a [char],
-    line: &'a [char],
-    next_line: &'a [char],
-    prev_user_text: &'a [char],
-    user_text: &'a [char],
+pub struct WidgetData<'a> {
+    /// Position of the first grapheme of `prev_line` in the text.
+    pub start: usize,
+    pub prev_line: &'a [Grapheme],
+    pub line: &'a [Grapheme],
+    pub next_line: &'a [Grapheme],
+    pub prev_user_text: &'a [Grapheme],
+    pub user_text: &'a [Grapheme],
 }
 
 impl<'a> WidgetData<'a> {
     fn empty() -> Self {
         WidgetData {
+            start: 0,
             prev_line: &[],
             line: &[],
             next_line: &[],
@@ -121,68 +405,192 @@ impl<'a> WidgetData<'a> {
     }
 }
 
-mod widget {
-    use ratatui::{
-        buffer::Buffer,
-        layout::Rect,
-        widgets::{StatefulWidget, Widget},
-    };
-
-    use crate::{langs::WordSupplier, layout::TestLines};
-
-    use super::{TextManager, WidgetData};
-
-    impl<Ws: WordSupplier> StatefulWidget for &mut TextManager<Ws> {
-        type State = Option<(u16, u16)>;
-        fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-            let WidgetData {
-                prev_line,
-                line,
-                next_line,
-                prev_user_text,
-                user_text,
-            } = self.widget_data(area.width as usize);
-            let text = TestLines::new(prev_line, line, next_line, prev_user_text, user_text);
-            text.render(area, buf);
-            let cursor = if user_text.is_empty() && prev_user_text.len() < prev_line.len() {
-                (area.left() + prev_user_text.len() as u16, area.top())
-            } else {
-                (area.left() + user_text.len() as u16, area.top() + 1)
-            };
-            *state = Some(cursor);
-        }
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::code::{Snippet, WordSupplierCode};
 
     #[test]
     fn get_widget_correct_width() {
-        let mut text_manager = TextManager::new(WordSupplierRandomized::new("english").unwrap());
+        let mut text_manager = TextManager::new(WordSupplierRandomized::new("english", 0).unwrap());
         for width in 1..1000 {
             let line = text_manager.widget_data(width).prev_line;
-            assert!(line.len() <= width);
+            assert!(display_width(line) <= width);
         }
     }
 
     #[test]
     fn too_short_width() {
-        let mut text_manager = TextManager::new(WordSupplierRandomized::new("english").unwrap());
+        let mut text_manager = TextManagerBasic::new(WordSupplierBasic::new("日本語"));
         let line = text_manager.widget_data(1).prev_line;
         assert!(line.is_empty());
     }
 
+    #[test]
+    fn long_words_are_split() {
+        let text = format!("aa bb {} cc dd ee ff gg", "x".repeat(50));
+        let mut text_manager = TextManagerBasic::new(WordSupplierBasic::new(&text));
+        for c in text[..text.find(" ee").unwrap()].chars() {
+            text_manager.handle_char(c);
+        }
+        let data = text_manager.widget_data(40);
+        assert_eq!(data.prev_line.len(), 40);
+        assert_eq!(data.line.concat(), "xxxxxxxxxx cc dd ee ff gg ");
+        assert!(data.next_line.is_empty());
+    }
+
     #[test]
     fn max_width_achieved() {
-        let mut text_manager = TextManager::new(WordSupplierRandomized::new("english").unwrap());
+        let mut text_manager = TextManager::new(WordSupplierRandomized::new("english", 0).unwrap());
         for width in 1..1000 {
             let line = text_manager.widget_data(width).prev_line;
-            if line.len() == width {
+            if display_width(line) == width {
                 return;
             }
         }
         panic!();
     }
+
+    #[test]
+    fn same_seed_gives_same_text() {
+        let mut text_manager =
+            TextManager::new(WordSupplierRandomized::new("english", 42).unwrap());
+        let mut words = WordSupplierRandomized::new("english", 42).unwrap();
+        for _ in 0..5 {
+            for c in words.get_word().unwrap().chars().chain([' ']) {
+                text_manager.handle_char(c);
+            }
+        }
+        assert_eq!(text_manager.words_completed(), 5);
+        assert_eq!(text_manager.accuracy(), 100f64);
+    }
+
+    #[test]
+    fn words_completed_counts_finished_words() {
+        let mut text_manager = TextManagerBasic::new(WordSupplierBasic::new("ab ab ab"));
+        assert_eq!(text_manager.words_completed(), 0);
+        text_manager.handle_char('a');
+        assert_eq!(text_manager.words_completed(), 0);
+        text_manager.handle_char('x');
+        assert_eq!(text_manager.words_completed(), 1);
+        text_manager.handle_char(' ');
+        text_manager.handle_char('a');
+        assert_eq!(text_manager.words_completed(), 1);
+        text_manager.handle_backspace();
+        text_manager.handle_backspace();
+        text_manager.handle_backspace();
+        assert_eq!(text_manager.words_completed(), 0);
+    }
+
+    #[test]
+    fn finite_supplier_ends_text() {
+        let mut text_manager = TextManagerBasic::new(WordSupplierBasic::new("ab cd"));
+        text_manager.widget_data(80);
+        for c in "ab c".chars() {
+            text_manager.handle_char(c);
+        }
+        assert!(!text_manager.text_finished());
+        text_manager.handle_char('d');
+        assert!(text_manager.text_finished());
+        text_manager.handle_char(' ');
+        assert_eq!(text_manager.typed(), 5);
+        assert_eq!(text_manager.words_completed(), 2);
+        assert_eq!(text_manager.widget_data(80).prev_line.len(), 6);
+    }
+
+    #[test]
+    fn breakdown_separates_mistakes() {
+        let mut text_manager = TextManagerBasic::new(WordSupplierBasic::new("ab ab ab"));
+        text_manager.widget_data(80);
+        for c in "xbbabx".chars() {
+            text_manager.handle_char(c);
+        }
+        text_manager.handle_backspace();
+        text_manager.handle_char(' ');
+        text_manager.handle_char(' ');
+        let breakdown = text_manager.breakdown();
+        assert_eq!(
+            breakdown,
+            CharBreakdown {
+                correct: 4,
+                incorrect: 1,
+                extra: 1,
+                missed: 1,
+                corrected: 1,
+            }
+        );
+        assert_eq!(text_manager.accuracy(), 50f64);
+    }
+
+    #[test]
+    fn wide_graphemes_wrap_by_width() {
+        let mut text_manager =
+            TextManagerBasic::new(WordSupplierBasic::new(&"日本語 ".repeat(100)));
+        for width in 7..40 {
+            let line = text_manager.widget_data(width).prev_line;
+            assert_eq!(line.len(), width / 7 * 4);
+            assert!(display_width(line) <= width);
+        }
+    }
+
+    #[test]
+    fn combining_marks_form_one_grapheme() {
+        let mut text_manager =
+            TextManagerBasic::new(WordSupplierBasic::new("cafe\u{301} cafe\u{301}"));
+        text_manager.widget_data(80);
+        for c in "cafe\u{301} ".chars() {
+            text_manager.handle_char(c);
+        }
+        assert_eq!(text_manager.typed(), 5);
+        assert_eq!(text_manager.errors(), 0);
+        for c in "cafe".chars() {
+            text_manager.handle_char(c);
+        }
+        assert_eq!(text_manager.typed(), 8);
+        text_manager.handle_backspace();
+        text_manager.handle_char('x');
+        assert_eq!(text_manager.errors(), 1);
+        text_manager.handle_backspace();
+        text_manager.handle_char('\u{e9}');
+        assert_eq!(text_manager.breakdown().correct, 9);
+    }
+
+    #[test]
+    fn enter_ends_lines_and_indentation_is_skipped() {
+        let snippet = Snippet {
+            lines: vec!["a {".to_string(), "  b".to_string(), "}".to_string()],
+            source: String::new(),
+        };
+        let mut text_manager = TextManager::new(WordSupplierCode::new(&snippet));
+        text_manager.set_skip_indent(true);
+        assert_eq!(text_manager.widget_data(2).line.len(), 4);
+        for c in "a {\nb\n}".chars() {
+            text_manager.handle_char(c);
+        }
+        assert!(text_manager.text_finished());
+        assert_eq!(text_manager.typed(), 7);
+        assert_eq!(text_manager.breakdown().correct, 7);
+        for _ in 0..4 {
+            text_manager.handle_backspace();
+        }
+        assert_eq!(text_manager.position(), 3);
+        text_manager.handle_char('\n');
+        assert_eq!(text_manager.position(), 6);
+        assert_eq!(text_manager.errors(), 0);
+    }
+
+    #[test]
+    fn delete_word_goes_back_one_word() {
+        let mut text_manager = TextManagerBasic::new(WordSupplierBasic::new("ab cd ef"));
+        text_manager.widget_data(80);
+        for c in "ab cx".chars() {
+            text_manager.handle_char(c);
+        }
+        text_manager.handle_delete_word();
+        assert_eq!(text_manager.position(), 3);
+        text_manager.handle_delete_word();
+        assert_eq!(text_manager.position(), 0);
+        assert_eq!(text_manager.breakdown().corrected, 1);
+        assert_eq!(text_manager.words(), ["ab", "cd", "ef"]);
+    }
 }
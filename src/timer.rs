@@ -1,64 +1,107 @@
-use std::{
-    cell::Cell,
-    time::{Duration, SystemTime},
-};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
 
 pub fn wpm_from_letters(letters: usize, time: Duration) -> f64 {
     letters as f64 * 12000f64 / time.as_millis() as f64
 }
 
-pub struct TimeManager {
-    start: SystemTime,
-    duration: Duration,
-    last_wpm_update: Cell<SystemTime>,
-    last_wpm: Cell<usize>,
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sample {
+    /// Seconds since the start of the test.
+    pub time: f64,
+    /// Speed of all keystrokes made since the previous sample.
+    pub raw_wpm: f64,
+    /// Speed of correct letters since the start of the test.
+    pub wpm: f64,
+    /// Incorrect keystrokes made since the previous sample.
+    pub errors: usize,
 }
 
-impl TimeManager {
-    pub fn new(duration: Duration) -> Self {
-        let start = SystemTime::now();
-        TimeManager {
-            start,
-            duration,
-            last_wpm_update: Cell::new(start),
-            last_wpm: Cell::new(0),
-        }
+#[derive(Default)]
+pub struct Sampler {
+    samples: Vec<Sample>,
+    last_time: Duration,
+    last_keystrokes: usize,
+    last_errors: usize,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        Self::default()
     }
-    pub fn time_expired(&self) -> bool {
-        if let Ok(duration) = self.start.elapsed() {
-            return duration > self.duration;
+    /// Records a sample once every full second of the test.
+    pub fn update(&mut self, elapsed: Duration, correct: usize, keystrokes: usize, errors: usize) {
+        if elapsed.as_secs() as usize > self.samples.len() {
+            self.sample(elapsed, correct, keystrokes, errors);
         }
-        false
     }
-    pub fn percent_elapsed(&self) -> u16 {
-        if let Ok(milis) = self.start.elapsed().map(|t| t.as_millis() as f64) {
-            let total = self.duration.as_millis() as f64;
-            return (milis * 100f64 / total) as u16;
-        }
-        0
+    fn sample(&mut self, elapsed: Duration, correct: usize, keystrokes: usize, errors: usize) {
+        self.samples.push(Sample {
+            time: elapsed.as_secs_f64(),
+            raw_wpm: wpm_from_letters(keystrokes - self.last_keystrokes, elapsed - self.last_time),
+            wpm: wpm_from_letters(correct, elapsed),
+            errors: errors - self.last_errors,
+        });
+        self.last_time = elapsed;
+        self.last_keystrokes = keystrokes;
+        self.last_errors = errors;
     }
-    pub fn duration(&self) -> Duration {
-        self.duration
+    /// Samples recorded so far, one per full second.
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
     }
-    fn real_wpm(&self, correct_letters: usize) -> usize {
-        if let Ok(duration) = self.start.elapsed() {
-            return wpm_from_letters(correct_letters, duration) as usize;
+    /// Closes the last, partial second and returns all samples.
+    pub fn finish(
+        &mut self,
+        elapsed: Duration,
+        correct: usize,
+        keystrokes: usize,
+        errors: usize,
+    ) -> Vec<Sample> {
+        if keystrokes != self.last_keystrokes && elapsed > self.last_time {
+            self.sample(elapsed, correct, keystrokes, errors);
         }
-        0
+        self.samples.clone()
     }
-    pub fn wpm(&self, correct_letters: usize) -> usize {
-        if let Ok(expired) = self
-            .last_wpm_update
-            .get()
-            .elapsed()
-            .map(|t| t >= Duration::from_secs(1))
-        {
-            if expired {
-                self.last_wpm.set(self.real_wpm(correct_letters));
-                self.last_wpm_update.set(SystemTime::now());
-            }
-            return self.last_wpm.get();
-        }
-        0
+}
+
+/// Consistency in percent: 100 minus the coefficient of variation of the raw
+/// per-second speed, so a perfectly even pace scores 100.
+pub fn consistency(samples: &[Sample]) -> f64 {
+    if samples.is_empty() {
+        return 0f64;
+    }
+    let len = samples.len() as f64;
+    let mean = samples.iter().map(|s| s.raw_wpm).sum::<f64>() / len;
+    if mean == 0f64 {
+        return 0f64;
+    }
+    let variance = samples
+        .iter()
+        .map(|s| (s.raw_wpm - mean).powi(2))
+        .sum::<f64>()
+        / len;
+    (100f64 - variance.sqrt() * 100f64 / mean).max(0f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sampler_records_every_second() {
+        let mut sampler = Sampler::new();
+        sampler.update(Duration::from_millis(500), 5, 5, 0);
+        sampler.update(Duration::from_millis(1000), 10, 11, 1);
+        sampler.update(Duration::from_millis(1500), 12, 14, 1);
+        let samples = sampler.finish(Duration::from_millis(2500), 20, 21, 2);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].raw_wpm, 132f64);
+        assert_eq!(samples[0].wpm, 120f64);
+        assert_eq!(samples[0].errors, 1);
+        assert_eq!(samples[1].raw_wpm, 80f64);
+        assert_eq!(samples[1].errors, 1);
+        assert!((consistency(&samples) - 75.47).abs() < 0.01);
     }
 }
@@ -1,28 +1,166 @@
-use crate::input::read_key_block;
+use std::io;
+
+use ratatui::crossterm::event::KeyCode;
+
+use crate::{
+    code::available_code_languages,
+    daily::{load_daily, streak, today},
+    ghost::GhostMode,
+    input::read_key_block,
+    keys::{load_key_stats, PRACTICE_TARGETS},
+    langs::available_languages,
+    layout::Theme,
+    profile::{list_profiles, Profile, MAX_NAME},
+    quotes::QuoteLength,
+};
 use tui_menu::{MenuEvent, MenuItem, MenuState};
 
+#[derive(Clone)]
 pub enum StartScreenAction {
     ChangeTime(u32),
+    ChangeWords(u32),
+    ChangeQuote(QuoteLength),
+    ChangeCode(String),
+    /// Back to the file given on the command line.
+    ChangeFile,
+    ChangePractice,
+    ChangeDaily,
+    ShowLeaderboard,
+    SwitchProfile(String),
+    NewProfile,
+    ChangeGhost(GhostMode),
+    ChangeGhostPace(u32),
+    ChangeLanguage(String),
+    ChangeTheme(Theme),
+    TogglePunctuation,
+    ToggleNumbers,
+    ToggleSkipIndent,
     Continue,
     Quit,
+    ShowStats,
     StartGame,
 }
 
 pub struct StartScreen {
-    menu: MenuState<u32>,
+    menu: MenuState<StartScreenAction>,
+    warnings: Vec<String>,
+    /// Keys and bigrams practice mode currently focuses on.
+    targets: Vec<String>,
+    /// Days in a row the daily challenge was done, and whether today's is.
+    streak: (u32, bool),
+    profile: String,
 }
 
 impl StartScreen {
-    pub fn new() -> Self {
+    pub fn new(profile: &Profile) -> Self {
+        Self::with_warnings(profile, vec![])
+    }
+    pub fn with_warnings(profile: &Profile, warnings: Vec<String>) -> Self {
+        use StartScreenAction::*;
+        let mut warnings = warnings;
+        let targets = match load_key_stats(profile) {
+            Ok(stats) => stats.weakest(PRACTICE_TARGETS),
+            Err(e) => {
+                warnings.push(format!("Could not read key statistics: {}", e));
+                vec![]
+            }
+        };
+        let streak = match load_daily() {
+            Ok(results) => streak(&results, profile.name(), today()),
+            Err(e) => {
+                warnings.push(format!("Could not read daily results: {}", e));
+                (0, false)
+            }
+        };
+        let mut profiles = list_profiles()
+            .iter()
+            .map(|p| MenuItem::item(p.name().to_string(), SwitchProfile(p.name().to_string())))
+            .collect::<Vec<_>>();
+        profiles.push(MenuItem::item("new profile", NewProfile));
         StartScreen {
-            menu: MenuState::<u32>::new(vec![MenuItem::group(
-                "Time",
-                vec![
-                    MenuItem::item("10 s", 10),
-                    MenuItem::item("30 s", 30),
-                    MenuItem::item("60 s", 60),
-                ],
-            )]),
+            warnings,
+            streak,
+            profile: profile.name().to_string(),
+            targets: targets.into_iter().map(|(key, _)| key).collect(),
+            menu: MenuState::new(vec![
+                MenuItem::group(
+                    "Time",
+                    vec![
+                        MenuItem::item("10 s", ChangeTime(10)),
+                        MenuItem::item("30 s", ChangeTime(30)),
+                        MenuItem::item("60 s", ChangeTime(60)),
+                    ],
+                ),
+                MenuItem::group(
+                    "Words",
+                    vec![
+                        MenuItem::item("10 words", ChangeWords(10)),
+                        MenuItem::item("25 words", ChangeWords(25)),
+                        MenuItem::item("50 words", ChangeWords(50)),
+                        MenuItem::item("100 words", ChangeWords(100)),
+                    ],
+                ),
+                MenuItem::group(
+                    "Quote",
+                    vec![
+                        MenuItem::item("short", ChangeQuote(QuoteLength::Short)),
+                        MenuItem::item("medium", ChangeQuote(QuoteLength::Medium)),
+                        MenuItem::item("long", ChangeQuote(QuoteLength::Long)),
+                        MenuItem::item("any", ChangeQuote(QuoteLength::Any)),
+                    ],
+                ),
+                MenuItem::group(
+                    "Code",
+                    available_code_languages()
+                        .into_iter()
+                        .map(|lang| MenuItem::item(lang.clone(), ChangeCode(lang)))
+                        .collect(),
+                ),
+                MenuItem::item("File", ChangeFile),
+                MenuItem::item("Practice", ChangePractice),
+                MenuItem::group(
+                    "Daily",
+                    vec![
+                        MenuItem::item("today's challenge", ChangeDaily),
+                        MenuItem::item("leaderboard", ShowLeaderboard),
+                    ],
+                ),
+                MenuItem::group(
+                    "Text",
+                    vec![
+                        MenuItem::item("punctuation", TogglePunctuation),
+                        MenuItem::item("numbers", ToggleNumbers),
+                        MenuItem::item("skip indentation", ToggleSkipIndent),
+                    ],
+                ),
+                MenuItem::group(
+                    "Language",
+                    available_languages()
+                        .into_iter()
+                        .map(|lang| MenuItem::item(lang.clone(), ChangeLanguage(lang)))
+                        .collect(),
+                ),
+                MenuItem::group(
+                    "Ghost",
+                    vec![
+                        MenuItem::item("off", ChangeGhost(GhostMode::Off)),
+                        MenuItem::item("40 wpm", ChangeGhostPace(40)),
+                        MenuItem::item("60 wpm", ChangeGhostPace(60)),
+                        MenuItem::item("80 wpm", ChangeGhostPace(80)),
+                        MenuItem::item("100 wpm", ChangeGhostPace(100)),
+                        MenuItem::item("personal best", ChangeGhost(GhostMode::Best)),
+                    ],
+                ),
+                MenuItem::group(
+                    "Theme",
+                    [Theme::Default, Theme::Mono, Theme::Ocean]
+                        .into_iter()
+                        .map(|theme| MenuItem::item(theme.name(), ChangeTheme(theme)))
+                        .collect(),
+                ),
+                MenuItem::group("Profile", profiles),
+                MenuItem::item("Stats", ShowStats),
+            ]),
         }
     }
     pub fn handle_events(&mut self) -> StartScreenAction {
@@ -52,9 +190,9 @@ impl StartScreen {
 
         for e in self.menu.drain_events() {
             match e {
-                MenuEvent::Selected(time) => {
+                MenuEvent::Selected(selected) => {
                     self.menu.reset();
-                    action = ChangeTime(time);
+                    action = selected;
                 }
             }
         }
@@ -62,9 +200,37 @@ impl StartScreen {
     }
 }
 
-impl Default for StartScreen {
-    fn default() -> Self {
-        Self::new()
+pub enum ProfileAction {
+    Continue,
+    Cancel,
+    Create(Profile),
+}
+
+/// Asks for the name of a new profile.
+#[derive(Default)]
+pub struct NewProfileScreen {
+    name: String,
+    error: Option<String>,
+}
+
+impl NewProfileScreen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn handle_events(&mut self) -> io::Result<ProfileAction> {
+        match read_key_block()? {
+            KeyCode::Esc => return Ok(ProfileAction::Cancel),
+            KeyCode::Enter => match Profile::new(&self.name) {
+                Ok(profile) => return Ok(ProfileAction::Create(profile)),
+                Err(e) => self.error = Some(e.to_string()),
+            },
+            KeyCode::Backspace => {
+                self.name.pop();
+            }
+            KeyCode::Char(c) if self.name.chars().count() < MAX_NAME => self.name.push(c),
+            _ => {}
+        }
+        Ok(ProfileAction::Continue)
     }
 }
 
@@ -72,28 +238,102 @@ mod widget {
     use ratatui::prelude::*;
     use tui_menu::Menu;
 
-    use crate::app::GameOptions;
+    use crate::{
+        app::{GameMode, GameOptions},
+        ghost::GhostMode,
+    };
 
-    use super::StartScreen;
+    use super::{NewProfileScreen, StartScreen};
 
     impl StatefulWidget for &mut StartScreen {
         type State = GameOptions;
         fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
             use Constraint::*;
-            let [top, _, bot] = Layout::vertical([Length(1), Percentage(20), Fill(1)]).areas(area);
+            let warnings = self.warnings.len() as u16;
+            let [top, _, bot, warning_area] =
+                Layout::vertical([Length(1), Percentage(20), Fill(1), Length(warnings)])
+                    .areas(area);
             let option_area =
-                Layout::horizontal([Fill(1), Percentage(50), Fill(1)]).areas::<3>(bot)[1];
-            let [left, right] =
-                Layout::horizontal([Percentage(50), Percentage(50)]).areas(option_area);
+                Layout::horizontal([Fill(1), Percentage(80), Fill(1)]).areas::<3>(bot)[1];
+            let [left, right] = Layout::horizontal([Length(28), Fill(1)]).areas(option_area);
+            let [profile_line, _, mode_line, language_line, text_line, ghost_line, theme_line, _, targets_line, streak_line] =
+                Layout::vertical([Length(1); 10]).areas(left);
+            Line::raw(format!("Profile: {}", self.profile))
+                .bold()
+                .left_aligned()
+                .render(profile_line, buf);
             Line::raw("Press Tab to start")
                 .bold()
                 .centered()
                 .render(top, buf);
-            Line::raw(format!("Time: {} s", state.time))
-                .bold()
+            let mode = match state.mode {
+                GameMode::Time => format!("Time: {} s", state.time),
+                GameMode::Words => format!("Words: {}", state.words),
+                GameMode::Quote => format!("Quote: {}", state.quote_length.name()),
+                GameMode::File => match &state.file {
+                    Some(file) => format!("File: {}", file.display()),
+                    None => "File".to_string(),
+                },
+                GameMode::Code => format!("Code: {}", state.code_language),
+                GameMode::Practice => format!("Practice: {} words", state.words),
+                GameMode::Daily => "Daily challenge".to_string(),
+            };
+            Line::raw(mode).bold().left_aligned().render(mode_line, buf);
+            Line::raw(format!("Language: {}", state.language))
+                .left_aligned()
+                .render(language_line, buf);
+            let text = match (state.punctuation, state.numbers) {
+                (false, false) => "words",
+                (true, false) => "punctuation",
+                (false, true) => "numbers",
+                (true, true) => "punct. + numbers",
+            };
+            Line::raw(format!("Text: {}", text))
                 .left_aligned()
-                .render(left, buf);
+                .render(text_line, buf);
+            let ghost = match state.ghost {
+                GhostMode::Off => "off".to_string(),
+                GhostMode::Pace => format!("{} wpm", state.ghost_wpm),
+                GhostMode::Best => "personal best".to_string(),
+            };
+            Line::raw(format!("Ghost: {}", ghost))
+                .left_aligned()
+                .render(ghost_line, buf);
+            Line::raw(format!("Theme: {}", state.theme.name()))
+                .left_aligned()
+                .render(theme_line, buf);
+            if !self.targets.is_empty() {
+                Line::raw(format!("Weak keys: {}", self.targets.join(" ")))
+                    .left_aligned()
+                    .render(targets_line, buf);
+            }
+            let streak = match self.streak {
+                (0, _) => "Daily: not played today".to_string(),
+                (days, true) => format!("Daily streak: {}, done ✓", days),
+                (days, false) => format!("Daily streak: {}, due today", days),
+            };
+            Line::raw(streak).left_aligned().render(streak_line, buf);
+            Text::from_iter(self.warnings.iter().map(|w| Line::raw(w.as_str()).red()))
+                .render(warning_area, buf);
             Menu::new().render(right, buf, &mut self.menu);
         }
     }
+
+    impl Widget for &NewProfileScreen {
+        fn render(self, area: Rect, buf: &mut Buffer) {
+            use Constraint::*;
+            let [top, _, name, error] =
+                Layout::vertical([Length(1), Percentage(20), Length(1), Length(1)]).areas(area);
+            Line::raw("New profile - Enter creates it, Esc returns")
+                .bold()
+                .centered()
+                .render(top, buf);
+            Line::from(vec!["Name: ".bold(), self.name.as_str().into(), "_".into()])
+                .centered()
+                .render(name, buf);
+            if let Some(e) = &self.error {
+                Line::raw(e.as_str()).red().centered().render(error, buf);
+            }
+        }
+    }
 }